@@ -0,0 +1,148 @@
+//! Derive macros for `json-order-test` (`KeyOrder`, `OrderedDocument`). Use them through the re-exports in
+//! that crate; the generated code refers to it as `::json_order_test`.
+
+mod serde_attrs;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use serde_attrs::{ContainerAttrs, FieldAttrs};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, LitStr};
+
+/// Implements `json_order_test::schema::KeyOrder`, taking the key order from
+/// the field declaration order and serde's renaming attributes.
+#[proc_macro_derive(KeyOrder, attributes(serde))]
+pub fn derive_key_order(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_key_order(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_key_order(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let container = ContainerAttrs::parse(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let mut properties = Vec::new();
+                for field in &fields.named {
+                    let attrs = FieldAttrs::parse(&field.attrs)?;
+                    if attrs.skip {
+                        continue;
+                    }
+                    let ty = &field.ty;
+                    let schema =
+                        quote!(<#ty as ::json_order_test::schema::KeyOrder>::ordering_schema());
+                    if attrs.flatten {
+                        // A flattened map's entries become the parent's
+                        // additional properties.
+                        properties.push(quote! {
+                            if let ::json_order_test::schema::OrderingSchema::Object { properties: flattened, additional: extra } = #schema {
+                                properties.extend(flattened);
+                                if extra.is_some() {
+                                    additional = extra;
+                                }
+                            }
+                        });
+                    } else {
+                        let ident = field.ident.as_ref().expect("named field");
+                        let key = attrs
+                            .rename
+                            .unwrap_or_else(|| container.rename_field(&ident.to_string()));
+                        properties.push(quote! {
+                            properties.push((::std::string::String::from(#key), #schema));
+                        });
+                    }
+                }
+                quote! {
+                    let mut properties = ::std::vec::Vec::new();
+                    let mut additional = ::std::option::Option::None;
+                    #(#properties)*
+                    ::json_order_test::schema::OrderingSchema::Object { properties, additional }
+                }
+            }
+            // A newtype serializes as its inner value.
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote!(<#ty as ::json_order_test::schema::KeyOrder>::ordering_schema())
+            }
+            Fields::Unnamed(_) | Fields::Unit => {
+                quote!(::json_order_test::schema::OrderingSchema::Any)
+            }
+        },
+        Data::Enum(_) => quote!(::json_order_test::schema::OrderingSchema::Any),
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "KeyOrder cannot be derived for unions",
+            ));
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::json_order_test::schema::KeyOrder));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::json_order_test::schema::KeyOrder for #name #ty_generics #where_clause {
+            fn ordering_schema() -> ::json_order_test::schema::OrderingSchema {
+                #body
+            }
+        }
+    })
+}
+
+/// Implements `json_order_test::OrderedDocument` and adds inherent `insert`
+/// and `fetch` helpers. Requires `Serialize`, `Deserialize` and `KeyOrder`.
+///
+/// The document type defaults to the type name and can be set with
+/// `#[document(doc_type = "...")]`.
+#[proc_macro_derive(OrderedDocument, attributes(document))]
+pub fn derive_ordered_document(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_ordered_document(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_ordered_document(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let mut doc_type = LitStr::new(&name.to_string(), name.span());
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("document"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("doc_type") {
+                doc_type = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `doc_type = \"...\"`"))
+            }
+        })?;
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::json_order_test::OrderedDocument for #name #ty_generics #where_clause {
+            const DOC_TYPE: &'static str = #doc_type;
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Stores this document with its fields in declaration order and
+            /// returns its id.
+            pub async fn insert(&self, store: &::json_order_test::OrderedJsonStore) -> ::json_order_test::Result<i32> {
+                store.insert_ordered(self).await
+            }
+
+            /// Reads back the document with the given id.
+            pub async fn fetch(store: &::json_order_test::OrderedJsonStore, id: i32) -> ::json_order_test::Result<Self> {
+                store.get_ordered::<Self>(id).await
+            }
+        }
+    })
+}
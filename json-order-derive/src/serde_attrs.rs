@@ -0,0 +1,129 @@
+//! The subset of `#[serde(...)]` attributes that affects key names and order.
+
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, LitStr, Token};
+
+#[derive(Default)]
+pub struct ContainerAttrs {
+    rename_all: Option<String>,
+}
+
+impl ContainerAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename_all") {
+                    parsed.rename_all = serialize_name(&meta)?;
+                    if let Some(rule) = &parsed.rename_all {
+                        if !RULES.contains(&rule.as_str()) {
+                            return Err(meta.error(format!("unknown rename_all rule {:?}", rule)));
+                        }
+                    }
+                    Ok(())
+                } else {
+                    skip(&meta)
+                }
+            })?;
+        }
+        Ok(parsed)
+    }
+
+    /// The serialized name of a field called `name` under `rename_all`.
+    pub fn rename_field(&self, name: &str) -> String {
+        let words = || name.split('_').filter(|word| !word.is_empty());
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_uppercase().chain(chars).collect()
+            })
+        };
+        match self.rename_all.as_deref() {
+            Some("lowercase") => name.to_lowercase(),
+            Some("UPPERCASE") => name.to_uppercase(),
+            Some("PascalCase") => words().map(capitalize).collect(),
+            Some("camelCase") => words()
+                .enumerate()
+                .map(|(index, word)| {
+                    if index == 0 {
+                        word.to_string()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            Some("SCREAMING_SNAKE_CASE") => name.to_uppercase(),
+            Some("kebab-case") => name.replace('_', "-"),
+            Some("SCREAMING-KEBAB-CASE") => name.replace('_', "-").to_uppercase(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+const RULES: &[&str] = &[
+    "lowercase",
+    "UPPERCASE",
+    "PascalCase",
+    "camelCase",
+    "snake_case",
+    "SCREAMING_SNAKE_CASE",
+    "kebab-case",
+    "SCREAMING-KEBAB-CASE",
+];
+
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub rename: Option<String>,
+    pub skip: bool,
+    pub flatten: bool,
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.rename = serialize_name(&meta)?;
+                    Ok(())
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                    parsed.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("flatten") {
+                    parsed.flatten = true;
+                    Ok(())
+                } else {
+                    skip(&meta)
+                }
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// Reads `key = "name"` or `key(serialize = "name", deserialize = "..")`.
+fn serialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<String>> {
+    if meta.input.peek(Token![=]) {
+        return Ok(Some(meta.value()?.parse::<LitStr>()?.value()));
+    }
+    let mut name = None;
+    meta.parse_nested_meta(|nested| {
+        if nested.path.is_ident("serialize") {
+            name = Some(nested.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else {
+            skip(&nested)
+        }
+    })?;
+    Ok(name)
+}
+
+/// Consumes an attribute this crate does not interpret.
+fn skip(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip(&nested))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,207 @@
+//! Detecting (and optionally repairing) drift between `raw_text` and
+//! `data_jsonb` in `json_test`.
+
+use crate::error::Result;
+use crate::jcs;
+use crate::store::OrderedJsonStore;
+use crate::verify::semantically_equal;
+use serde_json::Value;
+use sqlx::Row;
+use std::fmt;
+
+pub const DEFAULT_CHUNK_SIZE: i64 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditOptions {
+    /// Rows fetched per query.
+    pub chunk_size: i64,
+    /// Rewrite `data_jsonb` and `content_hash` from `raw_text`, which is
+    /// treated as the source of truth.
+    pub repair: bool,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            repair: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    /// `raw_text` is not valid JSON; cannot be repaired automatically.
+    InvalidRawText(String),
+    /// `raw_text` and `data_jsonb` hold different values.
+    ValueMismatch,
+    /// The values agree but `content_hash` does not match `raw_text`.
+    HashMismatch,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::InvalidRawText(error) => write!(f, "raw_text is not valid JSON: {}", error),
+            Drift::ValueMismatch => f.write_str("raw_text and data_jsonb differ"),
+            Drift::HashMismatch => f.write_str("content_hash does not match raw_text"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditIssue {
+    pub id: i32,
+    pub drift: Drift,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    pub scanned: u64,
+    pub issues: Vec<AuditIssue>,
+}
+
+impl AuditReport {
+    pub fn repaired(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.repaired).count()
+    }
+
+    /// Issues still present after the audit.
+    pub fn unresolved(&self) -> impl Iterator<Item = &AuditIssue> {
+        self.issues.iter().filter(|issue| !issue.repaired)
+    }
+
+    pub fn count(&self, matches: impl Fn(&Drift) -> bool) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| matches(&issue.drift))
+            .count()
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "scanned {} rows: {} invalid raw_text, {} value mismatches, {} hash mismatches, {} repaired",
+            self.scanned,
+            self.count(|drift| matches!(drift, Drift::InvalidRawText(_))),
+            self.count(|drift| matches!(drift, Drift::ValueMismatch)),
+            self.count(|drift| matches!(drift, Drift::HashMismatch)),
+            self.repaired()
+        )?;
+        for issue in &self.issues {
+            let status = if issue.repaired { " (repaired)" } else { "" };
+            write!(f, "\n  {}: {}{}", issue.id, issue.drift, status)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scans `json_test` in id order, `chunk_size` rows at a time, parsing each
+/// `raw_text` and comparing it with `data_jsonb` (ignoring key order) and
+/// with `content_hash`.
+///
+/// With `repair`, mismatched rows get `data_jsonb` and `content_hash`
+/// recomputed from `raw_text`. The update only applies if `raw_text` is still
+/// the text that was audited, so a concurrent edit is never overwritten. When
+/// `data_jsonb` is a generated column only the hash can be repaired.
+pub async fn audit(store: &OrderedJsonStore, options: AuditOptions) -> Result<AuditReport> {
+    let mut report = AuditReport::default();
+    let mut last_id = 0;
+
+    loop {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, data_jsonb, raw_text, content_hash
+            FROM json_test
+            WHERE id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+        )
+        .bind(last_id)
+        .bind(options.chunk_size.max(1))
+        .fetch_all(store.pool())
+        .await?;
+
+        let Some(last) = rows.last() else {
+            return Ok(report);
+        };
+        last_id = last.try_get("id")?;
+
+        for row in &rows {
+            report.scanned += 1;
+            let id: i32 = row.try_get("id")?;
+            let data_jsonb: Value = row.try_get("data_jsonb")?;
+            let raw_text: String = row.try_get("raw_text")?;
+            let content_hash: Option<String> = row.try_get("content_hash")?;
+
+            let parsed: Value = match serde_json::from_str(&raw_text) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    report.issues.push(AuditIssue {
+                        id,
+                        drift: Drift::InvalidRawText(e.to_string()),
+                        repaired: false,
+                    });
+                    continue;
+                }
+            };
+            let computed_hash = jcs::content_hash_exact(&parsed);
+            let drift = if !semantically_equal(&parsed, &data_jsonb) {
+                Drift::ValueMismatch
+            } else if content_hash.is_some_and(|stored| stored != computed_hash) {
+                Drift::HashMismatch
+            } else {
+                continue;
+            };
+
+            let repairable = drift == Drift::HashMismatch || !store.jsonb_is_generated();
+            let repaired = options.repair
+                && repairable
+                && repair(store, id, &raw_text, parsed, &computed_hash).await?;
+            report.issues.push(AuditIssue {
+                id,
+                drift,
+                repaired,
+            });
+        }
+    }
+}
+
+async fn repair(
+    store: &OrderedJsonStore,
+    id: i32,
+    raw_text: &str,
+    parsed: Value,
+    content_hash: &str,
+) -> Result<bool> {
+    let query = if store.jsonb_is_generated() {
+        sqlx::query(
+            r#"
+            UPDATE json_test
+            SET content_hash = $3
+            WHERE id = $1 AND raw_text = $2
+            "#,
+        )
+        .bind(id)
+        .bind(raw_text)
+        .bind(content_hash)
+    } else {
+        sqlx::query(
+            r#"
+            UPDATE json_test
+            SET data_jsonb = $3, content_hash = $4
+            WHERE id = $1 AND raw_text = $2
+            "#,
+        )
+        .bind(id)
+        .bind(raw_text)
+        .bind(parsed)
+        .bind(content_hash)
+    };
+    let result = query.execute(store.pool()).await?;
+    Ok(result.rows_affected() > 0)
+}
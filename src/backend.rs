@@ -0,0 +1,51 @@
+//! The storage operations shared by every backend, so code that only needs
+//! to store and fetch documents is not tied to PostgreSQL.
+
+use crate::error::Result;
+use crate::store::{OrderedJsonStore, StoredJson};
+use async_trait::async_trait;
+use serde_json::Value;
+
+#[async_trait]
+pub trait JsonStore: Send + Sync {
+    /// Creates or migrates whatever tables the backend needs.
+    async fn ensure_table_exists(&self) -> Result<()>;
+
+    /// Stores `json_data` and returns its id.
+    async fn insert_json(&self, json_data: &str) -> Result<i32>;
+
+    async fn get_json_by_id(&self, id: i32) -> Result<StoredJson>;
+
+    /// Every document, in id order.
+    async fn list(&self) -> Result<Vec<StoredJson>>;
+
+    /// Documents whose value at JSON Pointer `pointer` equals `value`,
+    /// ignoring key order and number spelling, in id order.
+    async fn find_by_pointer(&self, pointer: &str, value: &Value) -> Result<Vec<StoredJson>>;
+}
+
+#[async_trait]
+impl JsonStore for OrderedJsonStore {
+    async fn ensure_table_exists(&self) -> Result<()> {
+        OrderedJsonStore::ensure_table_exists(self).await
+    }
+
+    async fn insert_json(&self, json_data: &str) -> Result<i32> {
+        OrderedJsonStore::insert_json(self, json_data).await
+    }
+
+    async fn get_json_by_id(&self, id: i32) -> Result<StoredJson> {
+        OrderedJsonStore::get_json_by_id(self, id).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredJson>> {
+        OrderedJsonStore::list(self).await
+    }
+
+    /// Runs on `data_jsonb`, so it fails with
+    /// [`StoreError::NotQueryable`](crate::StoreError::NotQueryable) for
+    /// strategies without one.
+    async fn find_by_pointer(&self, pointer: &str, value: &Value) -> Result<Vec<StoredJson>> {
+        self.query().eq(pointer, value.clone()).fetch().await
+    }
+}
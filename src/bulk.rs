@@ -0,0 +1,262 @@
+//! High-throughput loading through `COPY ... FROM STDIN`.
+
+use crate::duplicates::resolve_duplicates;
+use crate::error::{Result, StoreError};
+use crate::jcs;
+use crate::manifest::KeyOrderManifest;
+use crate::ordered::OrderedJson;
+use crate::store::OrderedJsonStore;
+use crate::strategy::StorageStrategy;
+use serde::de::IgnoredAny;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BulkLoadSummary {
+    pub documents: u64,
+    /// Total size of the loaded documents' text.
+    pub bytes: u64,
+    pub chunks: u64,
+    pub elapsed: Duration,
+}
+
+impl BulkLoadSummary {
+    pub fn documents_per_second(&self) -> f64 {
+        self.documents as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn mib_per_second(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for BulkLoadSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "loaded {} documents ({} bytes) in {} chunks in {:.2?}: {:.0} docs/s, {:.2} MiB/s",
+            self.documents,
+            self.bytes,
+            self.chunks,
+            self.elapsed,
+            self.documents_per_second(),
+            self.mib_per_second()
+        )
+    }
+}
+
+/// Loads one document per non-blank line of `reader`, issuing one `COPY` per
+/// `chunk_size` lines.
+///
+/// Every line is validated before its chunk is sent, and an invalid line
+/// aborts the load with its line number; chunks already copied stay
+/// committed. Each line is stored byte for byte, just like
+/// [`OrderedJsonStore::insert_json`].
+pub async fn bulk_load_ndjson<R>(
+    store: &OrderedJsonStore,
+    reader: R,
+    chunk_size: usize,
+) -> Result<BulkLoadSummary>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut loader = Loader::new(store, chunk_size);
+    let mut lines = reader.lines();
+    let mut line_number = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+        if !line.trim().is_empty() {
+            loader.push(line_number, &line).await?;
+        }
+    }
+    loader.finish().await
+}
+
+/// Loads `documents` in chunks of `chunk_size`; see [`bulk_load_ndjson`].
+/// Errors report the 1-based position of the offending document as its line.
+pub async fn bulk_load<I, S>(
+    store: &OrderedJsonStore,
+    documents: I,
+    chunk_size: usize,
+) -> Result<BulkLoadSummary>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut loader = Loader::new(store, chunk_size);
+    for (index, document) in documents.into_iter().enumerate() {
+        loader.push(index + 1, document.as_ref()).await?;
+    }
+    loader.finish().await
+}
+
+struct Loader<'a> {
+    store: &'a OrderedJsonStore,
+    chunk_size: usize,
+    chunk: Vec<u8>,
+    rows: usize,
+    started: Instant,
+    summary: BulkLoadSummary,
+}
+
+impl<'a> Loader<'a> {
+    fn new(store: &'a OrderedJsonStore, chunk_size: usize) -> Self {
+        Self {
+            store,
+            chunk_size: chunk_size.max(1),
+            chunk: Vec::new(),
+            rows: 0,
+            started: Instant::now(),
+            summary: BulkLoadSummary::default(),
+        }
+    }
+
+    async fn push(&mut self, line: usize, json_data: &str) -> Result<()> {
+        let json_data = resolve_duplicates(json_data, self.store.duplicate_keys())
+            .map_err(|error| on_line(error, line))?;
+        let json_data = json_data.as_ref();
+        append_row(
+            &mut self.chunk,
+            self.store.strategy(),
+            self.store.jsonb_is_generated(),
+            json_data,
+        )
+        .map_err(|source| StoreError::InvalidLine { line, source })?;
+        self.rows += 1;
+        self.summary.documents += 1;
+        self.summary.bytes += json_data.len() as u64;
+        if self.rows == self.chunk_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut conn = self.store.pool().acquire().await?;
+        let mut copy = conn
+            .copy_in_raw(&copy_statement(
+                self.store.strategy(),
+                self.store.jsonb_is_generated(),
+            ))
+            .await?;
+        copy.send(self.chunk.as_slice()).await?;
+        copy.finish().await?;
+
+        self.chunk.clear();
+        self.rows = 0;
+        self.summary.chunks += 1;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<BulkLoadSummary> {
+        self.flush().await?;
+        self.summary.elapsed = self.started.elapsed();
+        Ok(self.summary)
+    }
+}
+
+/// Moves the position of an error within one document onto `line` of the
+/// input; documents are single lines.
+fn on_line(error: StoreError, line: usize) -> StoreError {
+    match error {
+        StoreError::Syntax {
+            column, message, ..
+        } => StoreError::Syntax {
+            line,
+            column,
+            message,
+        },
+        StoreError::DuplicateKey {
+            pointer, column, ..
+        } => StoreError::DuplicateKey {
+            pointer,
+            line,
+            column,
+        },
+        other => other,
+    }
+}
+
+/// `generated` leaves out `json_test.data_jsonb`, which PostgreSQL then
+/// derives from `raw_text`.
+fn copy_statement(strategy: StorageStrategy, generated: bool) -> String {
+    let columns = match strategy {
+        StorageStrategy::Dual if generated => "raw_text, content_hash",
+        StorageStrategy::Dual => "data_jsonb, raw_text, content_hash",
+        StorageStrategy::Text => "raw_text",
+        StorageStrategy::Json => "data_json",
+        StorageStrategy::JsonbManifest => "data_jsonb, key_order",
+    };
+    format!("COPY {} ({}) FROM STDIN", strategy.table(), columns)
+}
+
+/// Appends one row in `COPY` text format. The `jsonb` column receives the
+/// original text too, so PostgreSQL parses exactly what is stored in
+/// `raw_text`.
+fn append_row(
+    chunk: &mut Vec<u8>,
+    strategy: StorageStrategy,
+    generated: bool,
+    json_data: &str,
+) -> serde_json::Result<()> {
+    match strategy {
+        StorageStrategy::Dual => {
+            let content_hash = jcs::content_hash_exact_str(json_data)?;
+            if !generated {
+                append_field(chunk, json_data);
+                chunk.push(b'\t');
+            }
+            append_field(chunk, json_data);
+            chunk.push(b'\t');
+            append_field(chunk, &content_hash);
+        }
+        StorageStrategy::Text | StorageStrategy::Json => {
+            serde_json::from_str::<IgnoredAny>(json_data)?;
+            append_field(chunk, json_data);
+        }
+        StorageStrategy::JsonbManifest => {
+            let document: OrderedJson = json_data.parse()?;
+            let manifest = KeyOrderManifest::from_document(&document);
+            append_field(chunk, json_data);
+            chunk.push(b'\t');
+            append_field(chunk, &serde_json::to_string(&manifest)?);
+        }
+    }
+    chunk.push(b'\n');
+    Ok(())
+}
+
+/// Escapes the characters that are special in `COPY` text format.
+fn append_field(chunk: &mut Vec<u8>, text: &str) {
+    for &byte in text.as_bytes() {
+        match byte {
+            b'\\' => chunk.extend_from_slice(b"\\\\"),
+            b'\n' => chunk.extend_from_slice(b"\\n"),
+            b'\r' => chunk.extend_from_slice(b"\\r"),
+            b'\t' => chunk.extend_from_slice(b"\\t"),
+            _ => chunk.push(byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_escape_copy_specials() {
+        let mut chunk = Vec::new();
+        append_field(&mut chunk, "{\"a\": \"tab\\there\",\n\t\"b\": \"\r\\\\\"}");
+        assert_eq!(
+            String::from_utf8(chunk).unwrap(),
+            r#"{"a": "tab\\there",\n\t"b": "\r\\\\"}"#
+        );
+    }
+}
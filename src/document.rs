@@ -0,0 +1,17 @@
+//! Typed documents stored under a fixed document type. Implement with
+//! `#[derive(OrderedDocument)]`.
+
+use crate::schema::KeyOrder;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A Rust type persisted as one document per row, tagged with `DOC_TYPE`.
+///
+/// The derive also generates inherent `insert(&self, &store)` and
+/// `fetch(&store, id)` helpers that call
+/// [`OrderedJsonStore::insert_ordered`](crate::OrderedJsonStore::insert_ordered)
+/// and [`OrderedJsonStore::get_ordered`](crate::OrderedJsonStore::get_ordered).
+pub trait OrderedDocument: Serialize + DeserializeOwned + KeyOrder {
+    /// Defaults to the type name; override with `#[document(doc_type = "...")]`.
+    const DOC_TYPE: &'static str;
+}
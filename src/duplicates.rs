@@ -0,0 +1,322 @@
+//! Duplicate object keys: finding them and deciding which occurrence is
+//! stored.
+//!
+//! `serde_json` and `jsonb` silently keep the last of duplicate keys while
+//! `raw_text` keeps every occurrence. A [`DuplicateKeys`] policy other than
+//! [`PreserveAll`](DuplicateKeys::PreserveAll) resolves the text before it is
+//! stored, so every column holds the same members.
+
+use crate::error::{Result, StoreError};
+use crate::ordered::join_pointer;
+use crate::span::{line_column, SpannedJson, SpannedKind};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// What to do with an object that repeats a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DuplicateKeys {
+    /// Fail with [`StoreError::DuplicateKey`] naming the first repeat.
+    Reject,
+    /// Drop every occurrence after the first from the text.
+    KeepFirst,
+    /// Drop every occurrence before the last from the text, matching what
+    /// `jsonb` keeps.
+    KeepLast,
+    /// Store the text untouched. `raw_text` and `json` columns keep every
+    /// occurrence (read them back as an ordered multimap with
+    /// [`SpannedJson`]); `jsonb` keeps the last.
+    #[default]
+    PreserveAll,
+}
+
+impl FromStr for DuplicateKeys {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(DuplicateKeys::Reject),
+            "keep-first" => Ok(DuplicateKeys::KeepFirst),
+            "keep-last" => Ok(DuplicateKeys::KeepLast),
+            "preserve-all" => Ok(DuplicateKeys::PreserveAll),
+            other => Err(format!(
+                "unknown duplicate key policy {:?} (expected reject, keep-first, keep-last or preserve-all)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DuplicateKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicateKeys::Reject => "reject",
+            DuplicateKeys::KeepFirst => "keep-first",
+            DuplicateKeys::KeepLast => "keep-last",
+            DuplicateKeys::PreserveAll => "preserve-all",
+        })
+    }
+}
+
+/// A repeated key. Positions are 1-based line and column of the key's
+/// opening quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    /// JSON Pointer of the member.
+    pub pointer: String,
+    pub line: usize,
+    pub column: usize,
+    /// Where the key first occurred in the same object.
+    pub first_line: usize,
+    pub first_column: usize,
+}
+
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duplicate key {:?} at line {} column {} (first at line {} column {})",
+            self.pointer, self.line, self.column, self.first_line, self.first_column
+        )
+    }
+}
+
+/// Every repeated key in `text`, in document order.
+pub fn find_duplicates(text: &str) -> Result<Vec<Duplicate>> {
+    let root = SpannedJson::parse(text)?;
+    let mut found = Vec::new();
+    collect_duplicates(text, &root, "", &mut found);
+    Ok(found)
+}
+
+fn collect_duplicates(text: &str, node: &SpannedJson, pointer: &str, found: &mut Vec<Duplicate>) {
+    match &node.kind {
+        SpannedKind::Object(members) => {
+            let mut first_seen: HashMap<&str, usize> = HashMap::new();
+            for member in members {
+                let child = join_pointer(pointer, &member.key);
+                let first = *first_seen
+                    .entry(&member.key)
+                    .or_insert(member.key_span.start);
+                if first != member.key_span.start {
+                    let (line, column) = line_column(text, member.key_span.start);
+                    let (first_line, first_column) = line_column(text, first);
+                    found.push(Duplicate {
+                        pointer: child.clone(),
+                        line,
+                        column,
+                        first_line,
+                        first_column,
+                    });
+                }
+                collect_duplicates(text, &member.value, &child, found);
+            }
+        }
+        SpannedKind::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_duplicates(
+                    text,
+                    item,
+                    &join_pointer(pointer, &index.to_string()),
+                    found,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Applies `policy` to `text`, returning the text to store.
+///
+/// `KeepFirst` and `KeepLast` cut the dropped members out of the text (with
+/// their separating commas), leaving the formatting of everything else
+/// byte-identical. Text without duplicates is returned as is.
+pub fn resolve_duplicates(text: &str, policy: DuplicateKeys) -> Result<Cow<'_, str>> {
+    let keep_last = match policy {
+        DuplicateKeys::PreserveAll => return Ok(Cow::Borrowed(text)),
+        DuplicateKeys::Reject => {
+            return match find_duplicates(text)?.into_iter().next() {
+                Some(duplicate) => Err(StoreError::DuplicateKey {
+                    pointer: duplicate.pointer,
+                    line: duplicate.line,
+                    column: duplicate.column,
+                }),
+                None => Ok(Cow::Borrowed(text)),
+            };
+        }
+        DuplicateKeys::KeepFirst => false,
+        DuplicateKeys::KeepLast => true,
+    };
+
+    let root = SpannedJson::parse(text)?;
+    let mut cuts = Vec::new();
+    collect_cuts(&root, keep_last, &mut cuts);
+    if cuts.is_empty() {
+        return Ok(Cow::Borrowed(text));
+    }
+    cuts.sort_by_key(|cut| cut.start);
+    let mut resolved = String::with_capacity(text.len());
+    let mut from = 0;
+    for cut in cuts {
+        resolved.push_str(&text[from..cut.start]);
+        from = cut.end;
+    }
+    resolved.push_str(&text[from..]);
+    Ok(Cow::Owned(resolved))
+}
+
+/// Byte ranges to delete so that each object keeps one member per key. Only
+/// kept members are descended into, so the ranges never overlap.
+fn collect_cuts(node: &SpannedJson, keep_last: bool, cuts: &mut Vec<Range<usize>>) {
+    match &node.kind {
+        SpannedKind::Object(members) => {
+            let mut kept_index: HashMap<&str, usize> = HashMap::new();
+            for (index, member) in members.iter().enumerate() {
+                let kept = kept_index.entry(&member.key).or_insert(index);
+                if keep_last {
+                    *kept = index;
+                }
+            }
+            let kept: Vec<bool> = members
+                .iter()
+                .enumerate()
+                .map(|(index, member)| kept_index[member.key.as_str()] == index)
+                .collect();
+
+            // Each run of dropped members goes up to the next kept key, or,
+            // at the end of the object, from the end of the previous kept
+            // value, which takes the comma with it.
+            let mut index = 0;
+            while index < members.len() {
+                if kept[index] {
+                    collect_cuts(&members[index].value, keep_last, cuts);
+                    index += 1;
+                    continue;
+                }
+                let run_start = index;
+                while index < members.len() && !kept[index] {
+                    index += 1;
+                }
+                let cut = match members.get(index) {
+                    Some(next) => members[run_start].key_span.start..next.key_span.start,
+                    None => {
+                        members[run_start - 1].value.span.end..members[index - 1].value.span.end
+                    }
+                };
+                cuts.push(cut);
+            }
+        }
+        SpannedKind::Array(items) => {
+            for item in items {
+                collect_cuts(item, keep_last, cuts);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "{\n  \"a\": 1,\n  \"b\": {\"c\": 1, \"c\": 2},\n  \"a\": 3\n}";
+
+    #[test]
+    fn finds_every_repeat_with_its_first_occurrence() {
+        let found = find_duplicates(TEXT).unwrap();
+        assert_eq!(
+            found,
+            [
+                Duplicate {
+                    pointer: "/b/c".to_string(),
+                    line: 3,
+                    column: 17,
+                    first_line: 3,
+                    first_column: 9,
+                },
+                Duplicate {
+                    pointer: "/a".to_string(),
+                    line: 4,
+                    column: 3,
+                    first_line: 2,
+                    first_column: 3,
+                },
+            ]
+        );
+        assert!(find_duplicates(r#"{"a": {"a": 1}}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reject_names_the_first_repeat() {
+        match resolve_duplicates(TEXT, DuplicateKeys::Reject) {
+            Err(StoreError::DuplicateKey {
+                pointer,
+                line,
+                column,
+            }) => assert_eq!((pointer.as_str(), line, column), ("/b/c", 3, 17)),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keep_first_cuts_later_members() {
+        assert_eq!(
+            resolve_duplicates(TEXT, DuplicateKeys::KeepFirst).unwrap(),
+            "{\n  \"a\": 1,\n  \"b\": {\"c\": 1}\n}"
+        );
+    }
+
+    #[test]
+    fn keep_last_cuts_earlier_members() {
+        assert_eq!(
+            resolve_duplicates(TEXT, DuplicateKeys::KeepLast).unwrap(),
+            "{\n  \"b\": {\"c\": 2},\n  \"a\": 3\n}"
+        );
+        assert_eq!(
+            resolve_duplicates(r#"{"a": 1, "a": 2, "a": 3}"#, DuplicateKeys::KeepLast).unwrap(),
+            r#"{"a": 3}"#
+        );
+    }
+
+    #[test]
+    fn dropped_members_are_not_searched() {
+        let text = r#"{"a": {"x": 1, "x": 2}, "a": 0}"#;
+        assert_eq!(
+            resolve_duplicates(text, DuplicateKeys::KeepFirst).unwrap(),
+            r#"{"a": {"x": 1}}"#
+        );
+        assert_eq!(
+            resolve_duplicates(text, DuplicateKeys::KeepLast).unwrap(),
+            r#"{"a": 0}"#
+        );
+    }
+
+    #[test]
+    fn untouched_text_is_borrowed() {
+        for policy in [DuplicateKeys::PreserveAll, DuplicateKeys::KeepLast] {
+            assert!(matches!(
+                resolve_duplicates(r#"{"a": 1}"#, policy).unwrap(),
+                Cow::Borrowed(_)
+            ));
+        }
+        assert!(matches!(
+            resolve_duplicates(TEXT, DuplicateKeys::PreserveAll).unwrap(),
+            Cow::Borrowed(TEXT)
+        ));
+    }
+
+    #[test]
+    fn policies_round_trip_through_their_names() {
+        for policy in [
+            DuplicateKeys::Reject,
+            DuplicateKeys::KeepFirst,
+            DuplicateKeys::KeepLast,
+            DuplicateKeys::PreserveAll,
+        ] {
+            assert_eq!(policy.to_string().parse::<DuplicateKeys>(), Ok(policy));
+        }
+        assert!("last".parse::<DuplicateKeys>().is_err());
+    }
+}
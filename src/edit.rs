@@ -0,0 +1,436 @@
+//! Format-preserving edits of JSON text, in the spirit of `toml_edit`.
+//!
+//! [`LosslessJson`] keeps the original text next to its [`SpannedJson`]
+//! tree. An edit rewrites only the bytes of the value it touches (plus a
+//! separating comma where members are added or removed); indentation,
+//! spacing, key order and escapes everywhere else stay byte-identical. New
+//! values copy the layout of their neighbours: the whitespace before the
+//! previous member, its `key: value` separator and the document's indent
+//! unit. JSON has no comments, so there are none to keep.
+
+use crate::error::Result;
+use crate::ordered::{parse_index, OrderedJson};
+use crate::patch::{array_insert_index, split_last, PatchTarget};
+use crate::span::{SpannedJson, SpannedKind};
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessJson {
+    text: String,
+    root: SpannedJson,
+}
+
+impl LosslessJson {
+    pub fn parse(text: impl Into<String>) -> Result<Self> {
+        let text = text.into();
+        let root = SpannedJson::parse(&text)?;
+        Ok(Self { text, root })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    pub fn root(&self) -> &SpannedJson {
+        &self.root
+    }
+
+    pub fn get(&self, pointer: &str) -> Option<&SpannedJson> {
+        self.root.pointer(pointer)
+    }
+
+    pub fn to_ordered(&self) -> OrderedJson {
+        self.root.to_ordered()
+    }
+
+    /// Replaces `range` with `replacement` and re-parses the text.
+    fn splice(&mut self, range: Range<usize>, replacement: &str) -> Result<(), String> {
+        let mut text = self.text.clone();
+        text.replace_range(range, replacement);
+        let root =
+            SpannedJson::parse(&text).map_err(|e| format!("edit produced invalid JSON: {}", e))?;
+        self.text = text;
+        self.root = root;
+        Ok(())
+    }
+
+    fn node(&self, path: &str) -> Result<&SpannedJson, String> {
+        self.get(path)
+            .ok_or_else(|| format!("no value at {:?}", path))
+    }
+
+    /// The container holding `path` and the unescaped last token.
+    fn parent(&self, path: &str) -> Result<(&SpannedJson, String), String> {
+        let (_, last) = split_last(path)?;
+        let (parent_path, _) = path
+            .rsplit_once('/')
+            .expect("split_last accepted a non-empty pointer");
+        Ok((self.node(parent_path)?, last))
+    }
+
+    /// Whether `path` names an object member (which `add` replaces in place
+    /// if it exists) rather than an array position (which `add` inserts at).
+    fn is_member(&self, path: &str) -> bool {
+        self.parent(path)
+            .is_ok_and(|(parent, _)| matches!(parent.kind, SpannedKind::Object(_)))
+    }
+
+    /// The whitespace immediately before byte `offset`.
+    fn whitespace_before(&self, offset: usize) -> &str {
+        let before = &self.text[..offset];
+        &before[before.trim_end_matches([' ', '\t', '\n', '\r']).len()..]
+    }
+
+    /// The leading whitespace of the line containing byte `offset`.
+    fn line_indent(&self, offset: usize) -> &str {
+        let line_start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = &self.text[line_start..];
+        &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+    }
+
+    /// The indentation step of the document, taken from its first indented
+    /// line; two spaces if it has none.
+    fn indent_unit(&self) -> &str {
+        self.text
+            .split('\n')
+            .skip(1)
+            .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+            .find(|indent| !indent.is_empty())
+            .unwrap_or("  ")
+    }
+
+    /// Serializes `value` compactly, or pretty-printed with the document's
+    /// indent unit when `multiline`, continuation lines starting at `indent`.
+    fn render(&self, value: &OrderedJson, indent: &str, multiline: bool) -> String {
+        let is_empty = match value {
+            OrderedJson::Object(map) => map.is_empty(),
+            OrderedJson::Array(items) => items.is_empty(),
+            _ => true,
+        };
+        if !multiline || is_empty {
+            return value.to_string();
+        }
+        let mut out = Vec::new();
+        let formatter = PrettyFormatter::with_indent(self.indent_unit().as_bytes());
+        value
+            .serialize(&mut Serializer::with_formatter(&mut out, formatter))
+            .expect("OrderedJson serialization cannot fail");
+        String::from_utf8(out)
+            .expect("serde_json emits UTF-8")
+            .replace('\n', &format!("\n{}", indent))
+    }
+
+    /// Where the entry for `path` starts in its container: the key of an
+    /// object member, the element itself in an array.
+    fn entry_start(&self, path: &str) -> Result<usize, String> {
+        let node = self.node(path)?;
+        if path.is_empty() {
+            return Ok(node.span.start);
+        }
+        let (parent, last) = self.parent(path)?;
+        Ok(match &parent.kind {
+            SpannedKind::Object(members) => members
+                .iter()
+                .rev()
+                .find(|member| member.key == last)
+                .map_or(node.span.start, |member| member.key_span.start),
+            _ => node.span.start,
+        })
+    }
+
+    fn set(&mut self, path: &str, value: &OrderedJson) -> Result<(), String> {
+        let node = self.node(path)?;
+        let span = node.span.clone();
+        let start = self.entry_start(path)?;
+        let multiline =
+            node.text(&self.text).contains('\n') || self.whitespace_before(start).contains('\n');
+        let rendered = self.render(value, self.line_indent(start), multiline);
+        self.splice(span, &rendered)
+    }
+
+    fn insert_member(
+        &mut self,
+        parent: &SpannedJson,
+        key: &str,
+        value: &OrderedJson,
+    ) -> Result<(), String> {
+        let SpannedKind::Object(members) = &parent.kind else {
+            unreachable!("caller checked the kind")
+        };
+        let key = serde_json::to_string(key).expect("strings always serialize");
+        let Some(last) = members.last() else {
+            let indent = self.line_indent(parent.span.start);
+            let rendered = if parent.text(&self.text).contains('\n') {
+                let inner = format!("{}{}", indent, self.indent_unit());
+                format!(
+                    "{{\n{}{}: {}\n{}}}",
+                    inner,
+                    key,
+                    self.render(value, &inner, true),
+                    indent
+                )
+            } else {
+                format!("{{{}:{}}}", key, value)
+            };
+            return self.splice(parent.span.clone(), &rendered);
+        };
+        let separator = &self.text[last.key_span.end..last.value.span.start];
+        let whitespace = match self.whitespace_before(last.key_span.start) {
+            gap if members.len() > 1 || gap.contains('\n') => gap,
+            // A lone member on one line: the gap after `{` is no guide, so
+            // space the comma like the member's colon.
+            _ if separator.ends_with([' ', '\t']) => " ",
+            _ => "",
+        };
+        let indent = self.line_indent(last.key_span.start);
+        let rendered = format!(
+            ",{}{}{}{}",
+            whitespace,
+            key,
+            separator,
+            self.render(value, indent, whitespace.contains('\n'))
+        );
+        let at = last.value.span.end;
+        self.splice(at..at, &rendered)
+    }
+
+    fn insert_element(
+        &mut self,
+        parent: &SpannedJson,
+        index: usize,
+        value: &OrderedJson,
+    ) -> Result<(), String> {
+        let SpannedKind::Array(items) = &parent.kind else {
+            unreachable!("caller checked the kind")
+        };
+        let Some(last) = items.last() else {
+            let inner = parent.span.start + 1..parent.span.end - 1;
+            return self.splice(inner, &value.to_string());
+        };
+        let neighbour = items.get(index).unwrap_or(last);
+        // The gap between two elements if there are two, else after `[`.
+        let whitespace = self.whitespace_before(items[1.min(items.len() - 1)].span.start);
+        let rendered = self.render(
+            value,
+            self.line_indent(neighbour.span.start),
+            whitespace.contains('\n'),
+        );
+        if index < items.len() {
+            let at = neighbour.span.start;
+            let rendered = format!("{},{}", rendered, whitespace);
+            self.splice(at..at, &rendered)
+        } else {
+            let at = last.span.end;
+            let rendered = format!(",{}{}", whitespace, rendered);
+            self.splice(at..at, &rendered)
+        }
+    }
+
+    /// Removes the entry for `path` (the last one, for a duplicated key) with
+    /// one adjoining comma.
+    fn remove_entry(&mut self, path: &str) -> Result<(), String> {
+        let (parent, last) = self.parent(path)?;
+        // Entry start..end ranges of the container's entries.
+        let entries: Vec<Range<usize>> = match &parent.kind {
+            SpannedKind::Object(members) => members
+                .iter()
+                .map(|member| member.key_span.start..member.value.span.end)
+                .collect(),
+            SpannedKind::Array(items) => items.iter().map(|item| item.span.clone()).collect(),
+            _ => return Err(format!("no value at {:?}", path)),
+        };
+        let index = match &parent.kind {
+            SpannedKind::Object(members) => members.iter().rposition(|member| member.key == last),
+            _ => parse_index(&last).filter(|&i| i < entries.len()),
+        }
+        .ok_or_else(|| format!("no value at {:?}", path))?;
+
+        let range = if entries.len() == 1 {
+            parent.span.start + 1..parent.span.end - 1
+        } else if index + 1 < entries.len() {
+            entries[index].start..entries[index + 1].start
+        } else {
+            entries[index - 1].end..entries[index].end
+        };
+        self.splice(range, "")
+    }
+}
+
+impl PatchTarget for LosslessJson {
+    fn value_at(&self, path: &str) -> Option<OrderedJson> {
+        self.get(path).map(SpannedJson::to_ordered)
+    }
+
+    fn is_object_at(&self, path: &str) -> bool {
+        self.get(path)
+            .is_some_and(|node| matches!(node.kind, SpannedKind::Object(_)))
+    }
+
+    fn add(&mut self, path: &str, value: OrderedJson) -> Result<(), String> {
+        if path.is_empty() || self.get(path).is_some_and(|_| self.is_member(path)) {
+            return self.set(path, &value);
+        }
+        let (parent, last) = self.parent(path)?;
+        let parent = parent.clone();
+        match &parent.kind {
+            SpannedKind::Object(_) => self.insert_member(&parent, &last, &value),
+            SpannedKind::Array(items) => {
+                let index = array_insert_index(&last, items.len(), path)?;
+                self.insert_element(&parent, index, &value)
+            }
+            _ => Err(format!("parent of {:?} is not a container", path)),
+        }
+    }
+
+    /// Removes the value at `path`. A duplicated key is removed with every
+    /// occurrence, since the earlier ones would otherwise become its value.
+    fn remove(&mut self, path: &str) -> Result<OrderedJson, String> {
+        let removed = self.node(path)?.to_ordered();
+        self.remove_entry(path)?;
+        while self.is_member(path) && self.get(path).is_some() {
+            self.remove_entry(path)?;
+        }
+        Ok(removed)
+    }
+
+    fn replace(&mut self, path: &str, value: OrderedJson) -> Result<(), String> {
+        self.set(path, &value)
+    }
+}
+
+impl fmt::Display for LosslessJson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordered_json;
+    use crate::patch::Patch;
+
+    fn patched(text: &str, patch: &str) -> String {
+        let mut document = LosslessJson::parse(text).unwrap();
+        Patch::parse(patch).unwrap().apply(&mut document).unwrap();
+        document.into_string()
+    }
+
+    #[test]
+    fn replace_touches_only_the_value() {
+        let text = "{\n    \"b\" :  1,\n    \"a\": \"\\u00e9\"\n}";
+        assert_eq!(
+            patched(text, r#"[{"op": "replace", "path": "/b", "value": 2}]"#),
+            "{\n    \"b\" :  2,\n    \"a\": \"\\u00e9\"\n}"
+        );
+    }
+
+    #[test]
+    fn added_members_copy_the_spacing_of_their_neighbours() {
+        let add = r#"[{"op": "add", "path": "/a", "value": 1}]"#;
+        assert_eq!(patched(r#"{"x": 1}"#, add), r#"{"x": 1, "a": 1}"#);
+        assert_eq!(patched(r#"{"x":1}"#, add), r#"{"x":1,"a":1}"#);
+        assert_eq!(patched(r#"{ "x": 1 }"#, add), r#"{ "x": 1, "a": 1 }"#);
+        assert_eq!(
+            patched(r#"{"x":1,  "y" : 2}"#, add),
+            r#"{"x":1,  "y" : 2,  "a" : 1}"#
+        );
+    }
+
+    #[test]
+    fn added_members_follow_the_indentation() {
+        let text = "{\n\t\"x\": {\n\t\t\"y\": 1\n\t}\n}";
+        assert_eq!(
+            patched(text, r#"[{"op": "add", "path": "/x/z", "value": {"k": [1]}}]"#),
+            "{\n\t\"x\": {\n\t\t\"y\": 1,\n\t\t\"z\": {\n\t\t\t\"k\": [\n\t\t\t\t1\n\t\t\t]\n\t\t}\n\t}\n}"
+        );
+    }
+
+    #[test]
+    fn empty_objects_keep_their_own_layout() {
+        let text = "{\n  \"inline\": {\"k\": 1},\n  \"block\": {\n  }\n}";
+        assert_eq!(
+            patched(
+                text,
+                r#"[
+                    {"op": "remove", "path": "/inline/k"},
+                    {"op": "add", "path": "/inline/z", "value": 3},
+                    {"op": "add", "path": "/block/z", "value": 3}
+                ]"#
+            ),
+            "{\n  \"inline\": {\"z\":3},\n  \"block\": {\n    \"z\": 3\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn remove_takes_one_comma_with_the_member() {
+        let remove = |path: &str| {
+            patched(
+                r#"{"a": 1, "b": 2, "c": 3}"#,
+                &format!(r#"[{{"op": "remove", "path": "{}"}}]"#, path),
+            )
+        };
+        assert_eq!(remove("/a"), r#"{"b": 2, "c": 3}"#);
+        assert_eq!(remove("/b"), r#"{"a": 1, "c": 3}"#);
+        assert_eq!(remove("/c"), r#"{"a": 1, "b": 2}"#);
+    }
+
+    #[test]
+    fn remove_drops_every_occurrence_of_a_duplicated_key() {
+        let text = r#"{"a": 1, "b": {"k": 1, "k": 2}, "a": 3}"#;
+        let remove = |path: &str| {
+            patched(
+                text,
+                &format!(r#"[{{"op": "remove", "path": "{}"}}]"#, path),
+            )
+        };
+        assert_eq!(remove("/a"), r#"{"b": {"k": 1, "k": 2}}"#);
+        assert_eq!(remove("/b/k"), r#"{"a": 1, "b": {}, "a": 3}"#);
+    }
+
+    #[test]
+    fn array_insertions_copy_the_element_gap() {
+        let text = "[1,  2]";
+        assert_eq!(
+            patched(text, r#"[{"op": "add", "path": "/0", "value": 0}]"#),
+            "[0,  1,  2]"
+        );
+        assert_eq!(
+            patched(text, r#"[{"op": "add", "path": "/-", "value": 3}]"#),
+            "[1,  2,  3]"
+        );
+        assert_eq!(
+            patched("[]", r#"[{"op": "add", "path": "/-", "value": 3}]"#),
+            "[3]"
+        );
+    }
+
+    #[test]
+    fn merge_patches_edit_in_place() {
+        let text = "{\n  \"b\": 1,\n  \"a\": {\"x\": 1, \"y\": 2}\n}";
+        assert_eq!(
+            patched(text, r#"{"a": {"y": null, "z": true}, "b": 5}"#),
+            "{\n  \"b\": 5,\n  \"a\": {\"x\": 1, \"z\": true}\n}"
+        );
+    }
+
+    #[test]
+    fn failed_patches_leave_the_text_alone() {
+        let text = r#"{"a": 1}"#;
+        let mut document = LosslessJson::parse(text).unwrap();
+        let patch = Patch::parse(
+            r#"[{"op": "add", "path": "/b", "value": 2}, {"op": "remove", "path": "/missing"}]"#,
+        )
+        .unwrap();
+        assert!(patch.apply(&mut document).is_err());
+        assert_eq!(document.as_str(), text);
+        assert_eq!(document.to_ordered(), ordered_json!({"a": 1}));
+    }
+}
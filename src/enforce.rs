@@ -0,0 +1,217 @@
+//! Database-side guarantees that `json_test.data_jsonb` matches `raw_text`,
+//! so rows written outside this crate (plain `psql`, other services) cannot
+//! diverge either.
+
+use crate::error::{Result, StoreError};
+use crate::migrate;
+use sqlx::{Executor, PgConnection, PgPool, Row};
+use std::fmt;
+use std::str::FromStr;
+
+const CONSTRAINT: &str = "json_test_raw_text_matches";
+const TRIGGER: &str = "json_test_derive_jsonb";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Enforcement {
+    /// Nothing beyond what the client writes.
+    #[default]
+    None,
+    /// A `CHECK (raw_text::jsonb = data_jsonb)` constraint rejects rows whose
+    /// columns disagree.
+    Check,
+    /// A `BEFORE INSERT OR UPDATE` trigger overwrites `data_jsonb` with
+    /// `raw_text::jsonb`, whatever the client sent.
+    Trigger,
+    /// `data_jsonb` is a stored generated column over `raw_text` and cannot
+    /// be written at all.
+    Generated,
+}
+
+impl Enforcement {
+    pub const ALL: [Enforcement; 4] = [
+        Enforcement::None,
+        Enforcement::Check,
+        Enforcement::Trigger,
+        Enforcement::Generated,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Enforcement::None => "none",
+            Enforcement::Check => "check",
+            Enforcement::Trigger => "trigger",
+            Enforcement::Generated => "generated",
+        }
+    }
+
+    /// Whether `data_jsonb` must be left out of inserts and updates.
+    pub fn derives_jsonb(&self) -> bool {
+        matches!(self, Enforcement::Generated)
+    }
+}
+
+impl FromStr for Enforcement {
+    type Err = StoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Enforcement::ALL
+            .into_iter()
+            .find(|enforcement| enforcement.as_str() == s)
+            .ok_or_else(|| StoreError::UnknownEnforcement(s.to_string()))
+    }
+}
+
+impl fmt::Display for Enforcement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The enforcement currently installed on `json_test`, read from the catalog.
+pub async fn current(pool: &PgPool) -> Result<Enforcement> {
+    current_in(&mut *pool.acquire().await?).await
+}
+
+async fn current_in(conn: &mut PgConnection) -> Result<Enforcement> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            EXISTS (
+                SELECT 1 FROM pg_attribute
+                WHERE attrelid = 'json_test'::regclass AND attname = 'data_jsonb' AND attgenerated = 's'
+            ) AS generated,
+            EXISTS (
+                SELECT 1 FROM pg_trigger
+                WHERE tgrelid = 'json_test'::regclass AND tgname = $1
+            ) AS has_trigger,
+            EXISTS (
+                SELECT 1 FROM pg_constraint
+                WHERE conrelid = 'json_test'::regclass AND conname = $2
+            ) AS has_check
+        "#,
+    )
+    .bind(TRIGGER)
+    .bind(CONSTRAINT)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(if row.try_get("generated")? {
+        Enforcement::Generated
+    } else if row.try_get("has_trigger")? {
+        Enforcement::Trigger
+    } else if row.try_get("has_check")? {
+        Enforcement::Check
+    } else {
+        Enforcement::None
+    })
+}
+
+/// Replaces whatever enforcement `json_test` has with `enforcement`. Safe to
+/// call repeatedly and concurrently; nothing changes if it is already in
+/// place.
+///
+/// Installing `check` fails if existing rows already disagree; run an audit
+/// with repair first. `trigger` and `generated` recompute `data_jsonb` for
+/// existing rows when they are installed.
+pub async fn apply(pool: &PgPool, enforcement: Enforcement) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(migrate::MIGRATION_LOCK_ID)
+        .execute(&mut *tx)
+        .await?;
+
+    let installed = current_in(&mut tx).await?;
+    if installed == enforcement {
+        tx.commit().await?;
+        return Ok(());
+    }
+
+    match installed {
+        Enforcement::None => {}
+        Enforcement::Check => {
+            tx.execute(r#"ALTER TABLE json_test DROP CONSTRAINT json_test_raw_text_matches"#)
+                .await?;
+        }
+        Enforcement::Trigger => {
+            tx.execute(
+                r#"
+                DROP TRIGGER json_test_derive_jsonb ON json_test;
+                DROP FUNCTION IF EXISTS json_test_derive_jsonb();
+                "#,
+            )
+            .await?;
+        }
+        Enforcement::Generated => {
+            tx.execute(r#"ALTER TABLE json_test ALTER COLUMN data_jsonb DROP EXPRESSION"#)
+                .await?;
+        }
+    }
+
+    match enforcement {
+        Enforcement::None => {}
+        Enforcement::Check => {
+            tx.execute(
+                r#"
+                ALTER TABLE json_test
+                ADD CONSTRAINT json_test_raw_text_matches CHECK (raw_text::jsonb = data_jsonb)
+                "#,
+            )
+            .await?;
+        }
+        Enforcement::Trigger => {
+            tx.execute(
+                r#"
+                CREATE OR REPLACE FUNCTION json_test_derive_jsonb() RETURNS trigger AS $$
+                BEGIN
+                    NEW.data_jsonb := NEW.raw_text::jsonb;
+                    RETURN NEW;
+                END;
+                $$ LANGUAGE plpgsql;
+
+                CREATE TRIGGER json_test_derive_jsonb
+                BEFORE INSERT OR UPDATE ON json_test
+                FOR EACH ROW EXECUTE FUNCTION json_test_derive_jsonb();
+
+                UPDATE json_test SET data_jsonb = raw_text::jsonb WHERE data_jsonb IS DISTINCT FROM raw_text::jsonb;
+                "#,
+            )
+            .await?;
+        }
+        Enforcement::Generated => {
+            tx.execute(
+                r#"
+                ALTER TABLE json_test DROP COLUMN data_jsonb;
+                ALTER TABLE json_test ADD COLUMN data_jsonb JSONB NOT NULL GENERATED ALWAYS AS (raw_text::jsonb) STORED;
+                "#,
+            )
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for enforcement in Enforcement::ALL {
+            assert_eq!(
+                enforcement.to_string().parse::<Enforcement>().unwrap(),
+                enforcement
+            );
+        }
+        assert!(matches!(
+            "strict".parse::<Enforcement>(),
+            Err(StoreError::UnknownEnforcement(name)) if name == "strict"
+        ));
+        let derived: Vec<Enforcement> = Enforcement::ALL
+            .into_iter()
+            .filter(Enforcement::derives_jsonb)
+            .collect();
+        assert_eq!(derived, [Enforcement::Generated]);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::strategy::StorageStrategy;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("invalid JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+
+    #[error("{message} at line {line} column {column}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("invalid JSON on line {line}: {source}")]
+    InvalidLine {
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("failed to read JSON file {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to read input: {0}")]
+    Read(#[from] std::io::Error),
+
+    #[error("no document with id {0}")]
+    NotFound(i32),
+
+    #[error("document id {0} does not fit in an i32")]
+    IdOutOfRange(i64),
+
+    #[error("document {0} has no key order manifest")]
+    MissingKeyOrder(i32),
+
+    #[error(
+        "content hash mismatch for document {id}: stored {stored}, raw_text hashes to {computed}"
+    )]
+    HashMismatch {
+        id: i32,
+        stored: String,
+        computed: String,
+    },
+
+    #[error("document {id} has type {found:?}, expected {expected:?}")]
+    DocTypeMismatch {
+        id: i32,
+        expected: &'static str,
+        found: Option<String>,
+    },
+
+    #[error("duplicate key {pointer:?} at line {line} column {column}")]
+    DuplicateKey {
+        pointer: String,
+        line: usize,
+        column: usize,
+    },
+
+    #[error("key {key:?} at {pointer:?} is not in the document's ordering schema")]
+    UnknownKey { pointer: String, key: String },
+
+    #[error("patch operation {index} failed: {message}")]
+    PatchFailed { index: usize, message: String },
+
+    #[error("patch operation {index}: test at {path:?} failed")]
+    PatchTestFailed { index: usize, path: String },
+
+    #[error("document {id} has no value at {pointer:?}")]
+    PointerNotFound { id: i32, pointer: String },
+
+    #[error("invalid JSON Pointer {0:?}")]
+    InvalidPointer(String),
+
+    #[error("storage strategy '{0}' has no jsonb column to query")]
+    NotQueryable(StorageStrategy),
+
+    #[error("unknown schema migration version {0}")]
+    UnknownMigration(i64),
+
+    #[error("unknown storage strategy {0:?} (expected dual, text, json or jsonb-manifest)")]
+    UnknownStrategy(String),
+
+    #[error("unknown enforcement {0:?} (expected none, check, trigger or generated)")]
+    UnknownEnforcement(String),
+}
+
+pub type Result<T, E = StoreError> = std::result::Result<T, E>;
@@ -0,0 +1,395 @@
+//! Everything a `jsonb` round trip loses, not just key order.
+//!
+//! `jsonb` keeps values but not their spelling: it drops insignificant
+//! whitespace, sorts keys, keeps only the last of duplicate keys, normalizes
+//! number text and re-escapes strings. [`compare`] lists each such
+//! difference between an original text and its `jsonb` value, by category
+//! and JSON Pointer.
+
+use crate::duplicates::find_duplicates;
+use crate::error::Result;
+use crate::ordered::{join_pointer, OrderedJson};
+use crate::span::{SpannedJson, SpannedKind};
+use crate::store::OrderedJsonStore;
+use crate::verify::{number_differences, verify};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::Row;
+use std::fmt;
+
+/// Examples shown per category by the `Display` impl.
+const EXAMPLES_SHOWN: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Indentation, newlines and spacing, which `jsonb` re-renders as
+    /// `{"a": 1, "b": [1, 2]}`.
+    Whitespace,
+    /// Object keys, which `jsonb` sorts shortest first.
+    KeyOrder,
+    /// Repeated keys, of which `jsonb` keeps the last.
+    DuplicateKeys,
+    /// Number spelling: `1e2` becomes `100`, `-0` becomes `0`.
+    NumberFormat,
+    /// String escapes: `"\u00e9"` becomes `"é"`, `"\/"` becomes `"/"`.
+    StringEscapes,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Whitespace,
+        Category::KeyOrder,
+        Category::DuplicateKeys,
+        Category::NumberFormat,
+        Category::StringEscapes,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Whitespace => "whitespace",
+            Category::KeyOrder => "key order",
+            Category::DuplicateKeys => "duplicate keys",
+            Category::NumberFormat => "number format",
+            Category::StringEscapes => "string escapes",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One place where the `jsonb` text differs from the original.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    pub category: Category,
+    pub pointer: String,
+    /// The original spelling.
+    pub original: String,
+    /// What `jsonb` returns instead, or drops (empty).
+    pub jsonb: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "(root)"
+        } else {
+            &self.pointer
+        };
+        let jsonb = if self.jsonb.is_empty() {
+            "(dropped)"
+        } else {
+            &self.jsonb
+        };
+        write!(f, "{}: {} -> {}", pointer, self.original, jsonb)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FidelityReport {
+    /// In category order, then document order.
+    pub differences: Vec<Difference>,
+}
+
+impl FidelityReport {
+    pub fn of(&self, category: Category) -> impl Iterator<Item = &Difference> {
+        self.differences
+            .iter()
+            .filter(move |difference| difference.category == category)
+    }
+
+    /// Whether the `jsonb` text is identical to the original.
+    pub fn lossless(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+impl fmt::Display for FidelityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lost = Category::ALL
+            .iter()
+            .filter(|&&category| self.of(category).next().is_some())
+            .count();
+        write!(
+            f,
+            "jsonb round trip changes {} of {} categories",
+            lost,
+            Category::ALL.len()
+        )?;
+        for category in Category::ALL {
+            let differences: Vec<&Difference> = self.of(category).collect();
+            if differences.is_empty() {
+                write!(f, "\n  {}: preserved", category)?;
+                continue;
+            }
+            write!(f, "\n  {}: {} changed", category, differences.len())?;
+            for difference in differences.iter().take(EXAMPLES_SHOWN) {
+                write!(f, "\n    {}", difference)?;
+            }
+            if differences.len() > EXAMPLES_SHOWN {
+                write!(
+                    f,
+                    "\n    ... and {} more",
+                    differences.len() - EXAMPLES_SHOWN
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends `json_data` through PostgreSQL's `jsonb` type, without storing it,
+/// and reports what the round trip changed.
+pub async fn round_trip(store: &OrderedJsonStore, json_data: &str) -> Result<FidelityReport> {
+    let row = sqlx::query("SELECT $1::jsonb AS data_jsonb")
+        .bind(json_data)
+        .fetch_one(store.pool())
+        .await?;
+    let data_jsonb: Value = row.try_get("data_jsonb")?;
+    compare(json_data, &data_jsonb)
+}
+
+/// Lists every difference between `original` and `jsonb`, the value
+/// PostgreSQL returned for it.
+pub fn compare(original: &str, jsonb: &Value) -> Result<FidelityReport> {
+    let root = SpannedJson::parse(original)?;
+    let mut differences = Vec::new();
+
+    let (before, after) = (&original[..root.span.start], &original[root.span.end..]);
+    if !before.is_empty() || !after.is_empty() {
+        differences.push(Difference {
+            category: Category::Whitespace,
+            pointer: String::new(),
+            original: format!("{:?} before and {:?} after the document", before, after),
+            jsonb: String::new(),
+        });
+    }
+    collect_whitespace(original, &root, String::new(), &mut differences);
+
+    let ordered: OrderedJson = original.parse()?;
+    for reordering in verify(&ordered, &OrderedJson::from(jsonb)).reordered {
+        differences.push(Difference {
+            category: Category::KeyOrder,
+            pointer: reordering.pointer,
+            original: format!("[{}]", reordering.original.join(", ")),
+            jsonb: format!("[{}]", reordering.retrieved.join(", ")),
+        });
+    }
+
+    for duplicate in find_duplicates(original)? {
+        let kept = jsonb
+            .pointer(&duplicate.pointer)
+            .map(Value::to_string)
+            .unwrap_or_default();
+        differences.push(Difference {
+            category: Category::DuplicateKeys,
+            pointer: duplicate.pointer,
+            original: format!(
+                "repeated at line {} column {} (first at line {} column {})",
+                duplicate.line, duplicate.column, duplicate.first_line, duplicate.first_column
+            ),
+            jsonb: format!("one member, {}", kept),
+        });
+    }
+
+    for number in number_differences(original, jsonb)? {
+        differences.push(Difference {
+            category: Category::NumberFormat,
+            pointer: number.pointer,
+            original: number.original,
+            jsonb: number.stored,
+        });
+    }
+
+    collect_escapes(original, &root, String::new(), &mut differences);
+    Ok(FidelityReport { differences })
+}
+
+/// The whitespace `jsonb` puts in a container: `": "` after keys, `", "`
+/// between entries and nothing inside the brackets.
+fn collect_whitespace(
+    source: &str,
+    node: &SpannedJson,
+    pointer: String,
+    out: &mut Vec<Difference>,
+) {
+    let inner = node.span.start + 1..node.span.end.saturating_sub(1);
+    // (gap, what jsonb writes there)
+    let mut gaps: Vec<(&str, &str)> = Vec::new();
+    let mut children: Vec<(String, &SpannedJson)> = Vec::new();
+    match &node.kind {
+        SpannedKind::Object(members) => {
+            let mut at = inner.start;
+            for (index, member) in members.iter().enumerate() {
+                let before = &source[at..member.key_span.start];
+                gaps.push((before, if index == 0 { "" } else { ", " }));
+                gaps.push((&source[member.key_span.end..member.value.span.start], ": "));
+                at = member.value.span.end;
+                children.push((join_pointer(&pointer, &member.key), &member.value));
+            }
+            gaps.push((&source[at..inner.end], ""));
+        }
+        SpannedKind::Array(items) => {
+            let mut at = inner.start;
+            for (index, item) in items.iter().enumerate() {
+                gaps.push((
+                    &source[at..item.span.start],
+                    if index == 0 { "" } else { ", " },
+                ));
+                at = item.span.end;
+                children.push((join_pointer(&pointer, &index.to_string()), item));
+            }
+            gaps.push((&source[at..inner.end], ""));
+        }
+        _ => return,
+    }
+
+    if let Some((gap, expected)) = gaps.into_iter().find(|(gap, expected)| gap != expected) {
+        out.push(Difference {
+            category: Category::Whitespace,
+            pointer: pointer.clone(),
+            original: format!("{:?}", gap),
+            jsonb: format!("{:?}", expected),
+        });
+    }
+    for (child_pointer, child) in children {
+        collect_whitespace(source, child, child_pointer, out);
+    }
+}
+
+/// Keys and strings whose source text is not how `jsonb` escapes them:
+/// only `"`, `\` and control characters, which is also what `serde_json`
+/// writes.
+fn collect_escapes(source: &str, node: &SpannedJson, pointer: String, out: &mut Vec<Difference>) {
+    match &node.kind {
+        SpannedKind::String(decoded) => {
+            out.extend(escape_difference(node.text(source), decoded, pointer, ""))
+        }
+        SpannedKind::Object(members) => {
+            for member in members {
+                let child = join_pointer(&pointer, &member.key);
+                out.extend(escape_difference(
+                    &source[member.key_span.clone()],
+                    &member.key,
+                    child.clone(),
+                    "key ",
+                ));
+                collect_escapes(source, &member.value, child, out);
+            }
+        }
+        SpannedKind::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_escapes(
+                    source,
+                    item,
+                    join_pointer(&pointer, &index.to_string()),
+                    out,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+fn escape_difference(
+    text: &str,
+    decoded: &str,
+    pointer: String,
+    label: &str,
+) -> Option<Difference> {
+    let escaped = serde_json::to_string(decoded).expect("strings always serialize");
+    (text != escaped).then(|| Difference {
+        category: Category::StringEscapes,
+        pointer,
+        original: format!("{}{}", label, text),
+        jsonb: format!("{}{}", label, escaped),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `jsonb_text` is what PostgreSQL 15 returns for `original::jsonb`.
+    fn report(original: &str, jsonb_text: &str) -> FidelityReport {
+        compare(original, &serde_json::from_str(jsonb_text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn jsonb_shaped_text_is_lossless() {
+        let text = r#"{"a": 1, "bb": [1.5, "x\n"], "ccc": {}}"#;
+        let report = report(text, text);
+        assert!(report.lossless());
+        assert!(report
+            .to_string()
+            .starts_with("jsonb round trip changes 0 of 5 categories\n  whitespace: preserved"));
+    }
+
+    #[test]
+    fn each_category_is_reported_by_pointer() {
+        let report = report(
+            "{\"bb\": 1e2, \"a\": \"caf\\u00e9\", \"a\": [ -0 ]}\n",
+            r#"{"a": [0], "bb": 100}"#,
+        );
+        let found: Vec<(Category, &str, &str, &str)> = report
+            .differences
+            .iter()
+            .map(|d| {
+                (
+                    d.category,
+                    d.pointer.as_str(),
+                    d.original.as_str(),
+                    d.jsonb.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Category::Whitespace,
+                    "",
+                    r#""" before and "\n" after the document"#,
+                    ""
+                ),
+                (Category::Whitespace, "/a", r#"" ""#, r#""""#),
+                (Category::KeyOrder, "", "[bb, a]", "[a, bb]"),
+                (
+                    Category::DuplicateKeys,
+                    "/a",
+                    "repeated at line 1 column 31 (first at line 1 column 13)",
+                    "one member, [0]"
+                ),
+                (Category::NumberFormat, "/bb", "1e2", "100"),
+                (Category::NumberFormat, "/a/0", "-0", "0"),
+                (Category::StringEscapes, "/a", r#""caf\u00e9""#, r#""café""#),
+            ]
+        );
+        assert!(!report.lossless());
+        assert_eq!(report.of(Category::NumberFormat).count(), 2);
+    }
+
+    #[test]
+    fn display_limits_examples_per_category() {
+        let unchanged = report("[1.0, 2.0, 3.0, 4.0, 5.0]", "[1.0, 2.0, 3.0, 4.0, 5.0]");
+        assert!(unchanged.lossless());
+        let report = report("[1e0, 2e0, 3e0, 4e0, 5e0]", "[1, 2, 3, 4, 5]");
+        assert_eq!(
+            report.to_string(),
+            "jsonb round trip changes 1 of 5 categories\n  \
+             whitespace: preserved\n  \
+             key order: preserved\n  \
+             duplicate keys: preserved\n  \
+             number format: 5 changed\n    \
+             /0: 1e0 -> 1\n    \
+             /1: 2e0 -> 2\n    \
+             /2: 3e0 -> 3\n    \
+             ... and 2 more\n  \
+             string escapes: preserved"
+        );
+    }
+}
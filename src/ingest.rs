@@ -0,0 +1,203 @@
+//! Batched ingestion of NDJSON (JSON Lines) input.
+
+use crate::error::Result;
+use crate::store::OrderedJsonStore;
+use sqlx::Connection;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// What happened to one non-blank input line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineResult {
+    /// 1-based line number in the input.
+    pub line: usize,
+    pub outcome: Result<i32, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngestReport {
+    /// One entry per non-blank line, in input order.
+    pub lines: Vec<LineResult>,
+}
+
+impl IngestReport {
+    /// Ids of the stored documents, in input order.
+    pub fn ids(&self) -> Vec<i32> {
+        self.lines
+            .iter()
+            .filter_map(|line| line.outcome.clone().ok())
+            .collect()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.lines.iter().filter_map(|line| {
+            line.outcome
+                .as_ref()
+                .err()
+                .map(|error| (line.line, error.as_str()))
+        })
+    }
+
+    pub fn inserted(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|line| line.outcome.is_ok())
+            .count()
+    }
+}
+
+/// Streams `reader` line by line and stores every line as a document, with
+/// `batch_size` lines per transaction.
+///
+/// A line that is not UTF-8 or fails to parse or insert is recorded in the report and rolled
+/// back to its savepoint; the rest of the batch is still committed. Only I/O
+/// errors on `reader` and failures to open a transaction abort ingestion.
+pub async fn ingest_ndjson<R>(
+    store: &OrderedJsonStore,
+    mut reader: R,
+    batch_size: usize,
+) -> Result<IngestReport>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let batch_size = batch_size.max(1);
+    let mut report = IngestReport::default();
+    let mut batch: Vec<(usize, Result<String, String>)> = Vec::with_capacity(batch_size);
+    let mut buffer = Vec::new();
+    let mut line_number = 0;
+
+    while let Some(line) = next_line(&mut reader, &mut buffer).await? {
+        line_number += 1;
+        if line.as_ref().is_ok_and(|line| line.trim().is_empty()) {
+            continue;
+        }
+        batch.push((line_number, line));
+        if batch.len() == batch_size {
+            insert_batch(store, &mut batch, &mut report).await?;
+        }
+    }
+    insert_batch(store, &mut batch, &mut report).await?;
+
+    Ok(report)
+}
+
+/// Reads the next line without its `\n` or `\r\n`, or `None` at the end of
+/// the input. A line that is not valid UTF-8 comes back as an `Err` with the
+/// decoding error, so only that line fails.
+async fn next_line<R>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+) -> Result<Option<Result<String, String>>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    buffer.clear();
+    if reader.read_until(b'\n', buffer).await? == 0 {
+        return Ok(None);
+    }
+    if buffer.ends_with(b"\n") {
+        buffer.pop();
+        if buffer.ends_with(b"\r") {
+            buffer.pop();
+        }
+    }
+    Ok(Some(
+        String::from_utf8(std::mem::take(buffer)).map_err(|error| error.to_string()),
+    ))
+}
+
+async fn insert_batch(
+    store: &OrderedJsonStore,
+    batch: &mut Vec<(usize, Result<String, String>)>,
+    report: &mut IngestReport,
+) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let first = report.lines.len();
+    let mut tx = store.pool().begin().await?;
+    for (line, json_data) in batch.drain(..) {
+        let json_data = match json_data {
+            Ok(json_data) => json_data,
+            Err(error) => {
+                report.lines.push(LineResult {
+                    line,
+                    outcome: Err(error),
+                });
+                continue;
+            }
+        };
+        let mut savepoint = Connection::begin(&mut *tx).await?;
+        let outcome = match store.insert_json_with(&mut savepoint, &json_data).await {
+            Ok(id) => {
+                savepoint.commit().await?;
+                Ok(id)
+            }
+            Err(error) => {
+                savepoint.rollback().await?;
+                Err(error.to_string())
+            }
+        };
+        report.lines.push(LineResult { line, outcome });
+    }
+
+    if let Err(error) = tx.commit().await {
+        let message = format!("batch commit failed: {}", error);
+        for line in &mut report.lines[first..] {
+            if line.outcome.is_ok() {
+                line.outcome = Err(message.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_split_ids_from_failures() {
+        let report = IngestReport {
+            lines: vec![
+                LineResult {
+                    line: 1,
+                    outcome: Ok(7),
+                },
+                LineResult {
+                    line: 3,
+                    outcome: Err("EOF while parsing".to_string()),
+                },
+                LineResult {
+                    line: 4,
+                    outcome: Ok(8),
+                },
+            ],
+        };
+        assert_eq!(report.ids(), [7, 8]);
+        assert_eq!(report.inserted(), 2);
+        assert_eq!(
+            report.failures().collect::<Vec<_>>(),
+            [(3, "EOF while parsing")]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_utf8_fails_only_its_own_line() {
+        let mut reader = &b"{\"a\": 1}\r\n{\"b\": \"\xff\"}\n\n[2]"[..];
+        let mut buffer = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(line) = next_line(&mut reader, &mut buffer).await.unwrap() {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], Ok(r#"{"a": 1}"#.to_string()));
+        assert!(lines[1]
+            .as_ref()
+            .is_err_and(|error| error.contains("invalid utf-8")));
+        assert_eq!(lines[2], Ok(String::new()));
+        assert_eq!(lines[3], Ok("[2]".to_string()));
+    }
+}
@@ -0,0 +1,330 @@
+//! JSON Canonicalization Scheme (RFC 8785) and SHA-256 content hashes.
+//!
+//! The canonical form sorts object keys by their UTF-16 code units, drops
+//! insignificant whitespace, and serializes numbers and strings the way
+//! ECMAScript's `JSON.stringify` does, so documents that differ only in
+//! formatting or key order hash to the same value.
+//!
+//! [`canonicalize`] follows RFC 8785 to the letter: numbers are read as
+//! doubles, so `333333333.33333329` is written `333333333.3333333` and
+//! `1e400` is rejected. Content hashes use [`canonicalize_exact`] instead,
+//! which writes a number whose double prints as a different value
+//! (`12345678901234567891`, `333333333.33333329`, `1e400`) as its exact
+//! decimal value, as significant digits and a power of ten, so documents
+//! that differ only in such low digits do not share a hash.
+
+use crate::verify::{exact_number_text, numbers_equal};
+use serde::ser::Error as _;
+use serde_json::{Number, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Parses `json_data` and returns its RFC 8785 canonical form.
+pub fn canonicalize_str(json_data: &str) -> serde_json::Result<String> {
+    canonicalize(&serde_json::from_str(json_data)?)
+}
+
+/// The RFC 8785 canonical form of `value`. Fails for numbers beyond the
+/// range of a double, which RFC 8785 cannot represent.
+pub fn canonicalize(value: &Value) -> serde_json::Result<String> {
+    let mut out = String::new();
+    write_value(&mut out, value, &double_text).ok_or_else(|| {
+        serde_json::Error::custom("number out of range for RFC 8785 (not a finite double)")
+    })?;
+    Ok(out)
+}
+
+/// Parses `json_data` and returns its [exact canonical form](canonicalize_exact).
+pub fn canonicalize_exact_str(json_data: &str) -> serde_json::Result<String> {
+    Ok(canonicalize_exact(&serde_json::from_str(json_data)?))
+}
+
+/// The RFC 8785 form of `value`, except that a number whose double prints as
+/// a different value is written as its exact decimal value.
+pub fn canonicalize_exact(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, &exact_text).expect("every number has an exact form");
+    out
+}
+
+/// Lowercase hex SHA-256 of the [exact canonical form](canonicalize_exact)
+/// of `value`.
+pub fn content_hash_exact(value: &Value) -> String {
+    let digest = Sha256::digest(canonicalize_exact(value).as_bytes());
+    digest
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+pub fn content_hash_exact_str(json_data: &str) -> serde_json::Result<String> {
+    Ok(content_hash_exact(&serde_json::from_str(json_data)?))
+}
+
+/// ECMAScript's serialization of the double nearest to `n`, or `None` if
+/// `n` is beyond the range of a double.
+fn double_text(n: &Number) -> Option<String> {
+    n.as_f64().filter(|x| x.is_finite()).map(number_text)
+}
+
+/// [`double_text`], or the exact decimal value where that would change it.
+fn exact_text(n: &Number) -> Option<String> {
+    Some(match double_text(n) {
+        Some(text)
+            if text
+                .parse()
+                .is_ok_and(|rounded: Number| numbers_equal(n, &rounded)) =>
+        {
+            text
+        }
+        _ => exact_number_text(n),
+    })
+}
+
+fn write_value(
+    out: &mut String,
+    value: &Value,
+    number: &impl Fn(&Number) -> Option<String>,
+) -> Option<()> {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&number(n)?),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_value(out, item, number)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (index, (key, child)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, child, number)?;
+            }
+            out.push('}');
+        }
+    }
+    Some(())
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < '\u{20}' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `x` as [`write_number`] writes it.
+fn number_text(x: f64) -> String {
+    let mut text = String::new();
+    write_number(&mut text, x);
+    text
+}
+
+/// ECMAScript `Number.prototype.toString` for finite doubles.
+fn write_number(out: &mut String, x: f64) {
+    if x == 0.0 || !x.is_finite() {
+        // JSON cannot express non-finite numbers; -0 serializes as 0.
+        out.push('0');
+        return;
+    }
+    if x < 0.0 {
+        out.push('-');
+    }
+
+    // serde_json writes the shortest round-tripping digits, e.g. "1.25e-7",
+    // and like ECMAScript picks the even digit on a tie: 1424953923781206.25
+    // is "1424953923781206.2" (Rust's `{:e}` rounds it up).
+    let shortest = Number::from_f64(x.abs())
+        .expect("finite doubles are numbers")
+        .to_string();
+    let (mantissa, exponent) = shortest.split_once(['e', 'E']).unwrap_or((&shortest, "0"));
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = format!("{}{}", integer, fraction);
+    let leading_zeros = all_digits.len() - all_digits.trim_start_matches('0').len();
+    let digits = all_digits.trim_matches('0');
+    let k = digits.len() as i32;
+    let n = exponent
+        .parse::<i32>()
+        .expect("serde_json writes integer exponents")
+        + integer.len() as i32
+        - leading_zeros as i32;
+
+    if k <= n && n <= 21 {
+        out.push_str(digits);
+        out.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat((-n) as usize));
+        out.push_str(digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push(if n - 1 < 0 { '-' } else { '+' });
+        let _ = write!(out, "{}", (n - 1).abs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordered::OrderedJson;
+    use serde_json::json;
+
+    #[test]
+    fn rfc8785_number_samples() {
+        // RFC 8785 Appendix B: IEEE 754 bit patterns and their serialization.
+        let samples: [(u64, &str); 22] = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+        for (bits, expected) in samples {
+            let value = json!(f64::from_bits(bits));
+            assert_eq!(canonicalize(&value).unwrap(), expected, "{:#018x}", bits);
+        }
+    }
+
+    #[test]
+    fn rfc8785_sorts_keys_by_utf16_code_units() {
+        // RFC 8785 section 3.2.3.
+        let canonical = canonicalize_str(
+            r#"{"€": "Euro Sign", "\r": "Carriage Return", "דּ": "Hebrew Letter Dalet With Dagesh",
+                "1": "One", "😀": "Emoji: Grinning Face", "\u0080": "Control",
+                "ö": "Latin Small Letter O With Diaeresis"}"#,
+        )
+        .unwrap();
+        let keys: Vec<String> = canonical
+            .parse::<OrderedJson>()
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "\r",
+                "1",
+                "\u{80}",
+                "\u{f6}",
+                "\u{20ac}",
+                "\u{1f600}",
+                "\u{fb33}"
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc8785_example() {
+        // RFC 8785 section 3.2.2.
+        let canonical = canonicalize_str(
+            r#"{
+                "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                "literals": [null, true, false]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            canonical,
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    #[test]
+    fn numbers_beyond_a_double_are_rejected() {
+        assert!(canonicalize_str("[1e400]").is_err());
+        assert_eq!(canonicalize_str("[1e308]").unwrap(), "[1e+308]");
+    }
+
+    #[test]
+    fn exact_form_keeps_numbers_a_double_cannot_hold() {
+        assert_eq!(
+            canonicalize_exact_str("333333333.33333329").unwrap(),
+            "33333333333333329e-8"
+        );
+        assert_eq!(
+            canonicalize_exact_str("12345678901234567890").unwrap(),
+            "1234567890123456789e1"
+        );
+        assert_eq!(canonicalize_exact_str("1e400").unwrap(), "1e400");
+        assert_eq!(canonicalize_exact_str("10e399").unwrap(), "1e400");
+        assert_eq!(
+            canonicalize_exact_str(r#"{"b": 4.50, "a": 1E30}"#).unwrap(),
+            canonicalize_str(r#"{"b": 4.50, "a": 1E30}"#).unwrap()
+        );
+        assert_ne!(
+            content_hash_exact_str("12345678901234567890").unwrap(),
+            content_hash_exact_str("12345678901234567891").unwrap()
+        );
+    }
+
+    #[test]
+    fn hash_ignores_whitespace_key_order_and_number_spelling() {
+        let hash = content_hash_exact_str(r#"{"b": [1.0, 2e0], "a": "x"}"#).unwrap();
+        assert_eq!(
+            hash,
+            content_hash_exact_str(r#"{"a":"x","b":[1,2]}"#).unwrap()
+        );
+        assert_eq!(hash.len(), 64);
+        assert!(hash
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+    }
+}
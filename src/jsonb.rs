@@ -0,0 +1,142 @@
+//! Model of how PostgreSQL's `jsonb` type normalizes documents.
+
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// The order in which `jsonb` stores object keys: shorter keys first, ties
+/// broken by bytewise comparison.
+pub fn key_cmp(a: &str, b: &str) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.as_bytes().cmp(b.as_bytes()))
+}
+
+/// Returns `keys` in the order `jsonb` would return them.
+pub fn key_order<'a, I: IntoIterator<Item = &'a String>>(keys: I) -> Vec<&'a String> {
+    let mut keys: Vec<&String> = keys.into_iter().collect();
+    keys.sort_by(|a, b| key_cmp(a, b));
+    keys
+}
+
+/// `value` as `jsonb` returns it: object keys in [`key_cmp`] order and
+/// numbers respelled by [`normalize_number`]. Duplicate keys are already
+/// collapsed to the last value by `serde_json`.
+pub fn normalize(value: &Value) -> Value {
+    match value {
+        Value::Number(n) => normalize_number(&n.to_string())
+            .parse()
+            .map_or_else(|_| value.clone(), Value::Number),
+        Value::Array(items) => Value::Array(items.iter().map(normalize).collect()),
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| key_cmp(a, b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, child)| (key.clone(), normalize(child)))
+                    .collect(),
+            )
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Digits `numeric` allows before and after the decimal point.
+const NUMERIC_MAX_WEIGHT: i64 = 131_072;
+const NUMERIC_MAX_SCALE: i64 = 16_383;
+
+/// The text PostgreSQL's `numeric` prints for a JSON number: exponents are
+/// expanded (`1e2` is `100`, `1.5e-2` is `0.015`), the scale of a plain
+/// decimal is kept (`1.50` stays `1.50`) and `-0` loses its sign.
+pub fn normalize_number(text: &str) -> String {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => match exponent.parse::<i64>() {
+            Ok(exponent) => (mantissa, exponent),
+            Err(_) => return text.to_string(),
+        },
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    // Power of ten of the last digit.
+    let last = exponent.saturating_sub(fraction.len() as i64);
+    if !(-NUMERIC_MAX_SCALE..=NUMERIC_MAX_WEIGHT).contains(&last) {
+        // Out of `numeric`'s range; PostgreSQL rejects it.
+        return text.to_string();
+    }
+
+    let mut out = String::new();
+    if negative && digits.bytes().any(|b| b != b'0') {
+        out.push('-');
+    }
+    if last >= 0 {
+        let whole = format!("{}{}", digits, "0".repeat(last as usize));
+        let whole = whole.trim_start_matches('0');
+        out.push_str(if whole.is_empty() { "0" } else { whole });
+    } else {
+        let scale = (-last) as usize;
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - scale);
+        let whole = whole.trim_start_matches('0');
+        out.push_str(if whole.is_empty() { "0" } else { whole });
+        out.push('.');
+        out.push_str(fraction);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_match_postgres() {
+        // What PostgreSQL 15 prints for `SELECT '[...]'::jsonb`.
+        let cases = [
+            ("1e2", "100"),
+            ("1.5e-2", "0.015"),
+            ("1.50", "1.50"),
+            ("-0", "0"),
+            ("-0.0", "0.0"),
+            ("1E+2", "100"),
+            ("0.000", "0.000"),
+            ("12e-5", "0.00012"),
+            ("-1.23e1", "-12.3"),
+            ("0.5e1", "5"),
+            ("10e-1", "1.0"),
+            ("100e-2", "1.00"),
+            ("1e0", "1"),
+            ("-5e-3", "-0.005"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(normalize_number(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn numbers_out_of_range_keep_their_text() {
+        assert_eq!(normalize_number("1e200000"), "1e200000");
+        assert_eq!(normalize_number("1e-20000"), "1e-20000");
+    }
+
+    #[test]
+    fn keys_sort_shortest_first_then_bytewise() {
+        let keys: Vec<String> = ["bb", "a", "aaa", "c", "ab"].map(String::from).to_vec();
+        assert_eq!(key_order(&keys), ["a", "c", "ab", "bb", "aaa"]);
+    }
+
+    #[test]
+    fn normalize_sorts_nested_keys_and_numbers() {
+        let value: Value =
+            serde_json::from_str(r#"{"zz": {"bb": 1e1, "a": [{"yy": -0, "x": 1.50}]}, "b": 2}"#)
+                .unwrap();
+        assert_eq!(
+            normalize(&value).to_string(),
+            r#"{"b":2,"zz":{"a":[{"x":1.50,"yy":0}],"bb":10}}"#
+        );
+    }
+}
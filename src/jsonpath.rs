@@ -0,0 +1,1212 @@
+//! SQL/JSON path queries (`jsonb_path_query`, `@?`) on `data_jsonb`, with
+//! matched sub-values mapped back into the original, ordered document.
+
+use crate::error::{Result, StoreError};
+use crate::jsonb::key_order;
+use crate::manifest::KeyOrderManifest;
+use crate::ordered::{join_pointer, OrderedJson};
+use crate::span::SpannedJson;
+use crate::store::OrderedJsonStore;
+use crate::verify::{compare_numbers, numbers_equal};
+use serde_json::{Number, Value};
+use sqlx::Row;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// One value selected by a path expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPathMatch {
+    pub id: i32,
+    /// Where the fragment sits in the stored document, or `None` if the path
+    /// computed it (e.g. `$.items.size()`) or could not be traced.
+    pub pointer: Option<String>,
+    /// The matched value with its original key order where it was located,
+    /// otherwise in JSONB order.
+    pub fragment: OrderedJson,
+    /// Byte range of the fragment in `raw_text`, for strategies that keep it.
+    pub span: Option<Range<usize>>,
+    /// The fragment's original text, formatting included.
+    pub text: Option<String>,
+}
+
+impl OrderedJsonStore {
+    /// Evaluates `path` (e.g. `$.movies[*] ? (@.year > 2010)`) against every
+    /// document and returns the selected values in document and match order.
+    ///
+    /// PostgreSQL decides what matches. To find where each match sits, the
+    /// path is also evaluated here over the ordered document, tracking the
+    /// pointer of every value it reaches. Those pointers are used only if
+    /// that evaluation selects the same values as PostgreSQL; otherwise, and
+    /// for syntax it does not cover (variables, `like_regex`, `.datetime()`,
+    /// `.keyvalue()`, ...), the matches come back without a pointer.
+    pub async fn query_jsonpath(&self, path: &str) -> Result<Vec<JsonPathMatch>> {
+        if !self.strategy().has_jsonb() {
+            return Err(StoreError::NotQueryable(self.strategy()));
+        }
+        let sql = format!(
+            r#"
+            SELECT {}, (SELECT jsonb_agg(m) FROM jsonb_path_query(data_jsonb, $1::jsonpath) AS m) AS matches
+            FROM {}
+            WHERE data_jsonb @? $1::jsonpath
+            ORDER BY id
+            "#,
+            self.strategy().columns(),
+            self.strategy().table()
+        );
+        let rows = sqlx::query(&sql).bind(path).fetch_all(self.pool()).await?;
+        let parsed = JsonPath::parse(path);
+
+        let mut results = Vec::new();
+        for row in &rows {
+            let stored = self.stored_from_row(row)?;
+            let document = stored.document()?;
+            let matches: Option<Value> = row.try_get("matches")?;
+            let values = match &matches {
+                Some(Value::Array(values)) => values.as_slice(),
+                _ => &[],
+            };
+            let items = parsed
+                .as_ref()
+                .and_then(|path| path.evaluate(&document))
+                .filter(|items| {
+                    items.len() == values.len()
+                        && items
+                            .iter()
+                            .zip(values)
+                            .all(|(item, value)| same_value(item.value(), value))
+                });
+            let spanned = match (&stored.raw_text, &items) {
+                (Some(raw_text), Some(_)) => Some((raw_text, SpannedJson::parse(raw_text)?)),
+                _ => None,
+            };
+
+            for (index, value) in values.iter().enumerate() {
+                let mut found = JsonPathMatch {
+                    id: stored.id,
+                    pointer: None,
+                    fragment: KeyOrderManifest::default().apply(value),
+                    span: None,
+                    text: None,
+                };
+                if let Some(Item::Node(pointer, node)) = items.as_ref().map(|items| &items[index]) {
+                    if let Some((raw_text, parsed)) = &spanned {
+                        if let Some(source) = parsed.pointer(pointer) {
+                            found.span = Some(source.span.clone());
+                            found.text = Some(source.text(raw_text).to_string());
+                        }
+                    }
+                    found.pointer = Some(pointer.clone());
+                    found.fragment = (*node).clone();
+                }
+                results.push(found);
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Order-insensitive equality between an ordered and a plain value.
+fn same_value(node: &OrderedJson, value: &Value) -> bool {
+    match (node, value) {
+        (OrderedJson::Null, Value::Null) => true,
+        (OrderedJson::Bool(a), Value::Bool(b)) => a == b,
+        (OrderedJson::Number(a), Value::Number(b)) => numbers_equal(a, b),
+        (OrderedJson::String(a), Value::String(b)) => a == b,
+        (OrderedJson::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        (OrderedJson::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same_value(a, b)))
+        }
+        _ => false,
+    }
+}
+
+/// A parsed SQL/JSON path. PostgreSQL decides what matches; this copy is
+/// evaluated over the ordered document only to learn where each match sits.
+#[derive(Debug, Clone, PartialEq)]
+struct JsonPath {
+    strict: bool,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Root,
+    Current,
+    Last,
+    Literal(OrderedJson),
+    Access(Box<Expr>, Accessor),
+    Negate(Box<Expr>),
+    Plus(Box<Expr>),
+    Arithmetic(Box<Expr>, char, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    StartsWith(Box<Expr>, Box<Expr>),
+    Exists(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    IsUnknown(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Accessor {
+    Member(String),
+    AnyMember,
+    AnyElement,
+    /// `[from to to, ...]`; a single index has no `to`.
+    Elements(Vec<(Expr, Option<Expr>)>),
+    /// `.**{first to last}`, with `u32::MAX` standing for `last`.
+    Descendants(u32, u32),
+    Filter(Box<Expr>),
+    Method(Method),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Type,
+    Size,
+    Double,
+    Ceiling,
+    Floor,
+    Abs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// SQL/JSON's three-valued logic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Truth {
+    True,
+    False,
+    Unknown,
+}
+
+impl From<bool> for Truth {
+    fn from(value: bool) -> Self {
+        if value {
+            Truth::True
+        } else {
+            Truth::False
+        }
+    }
+}
+
+/// A value reached while evaluating a path: a node of the document, with
+/// its pointer, or a value the path computed.
+#[derive(Debug, Clone, PartialEq)]
+enum Item<'a> {
+    Node(String, &'a OrderedJson),
+    Computed(OrderedJson),
+}
+
+impl<'a> Item<'a> {
+    fn value(&self) -> &OrderedJson {
+        match self {
+            Item::Node(_, value) => value,
+            Item::Computed(value) => value,
+        }
+    }
+
+    fn is_array(&self) -> bool {
+        matches!(self.value(), OrderedJson::Array(_))
+    }
+
+    /// The elements of an array node.
+    fn elements(&self) -> Vec<Item<'a>> {
+        match self {
+            Item::Node(pointer, OrderedJson::Array(items)) => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| Item::Node(join_pointer(pointer, &index.to_string()), item))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The members of an object node, in `jsonb` key order as PostgreSQL
+    /// visits them.
+    fn members(&self) -> Vec<Item<'a>> {
+        match self {
+            Item::Node(pointer, OrderedJson::Object(map)) => key_order(map.keys())
+                .into_iter()
+                .map(|key| Item::Node(join_pointer(pointer, key), &map[key]))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn member(&self, key: &str) -> Option<Item<'a>> {
+        match self {
+            Item::Node(pointer, OrderedJson::Object(map)) => map
+                .get(key)
+                .map(|value| Item::Node(join_pointer(pointer, key), value)),
+            _ => None,
+        }
+    }
+}
+
+/// Raised wherever PostgreSQL would raise an error.
+#[derive(Debug)]
+struct EvalError;
+
+type Eval<T> = std::result::Result<T, EvalError>;
+
+/// What `@` and `last` refer to, and whether structural errors are ignored
+/// even in strict mode, as they are after `.**`.
+#[derive(Clone, Copy)]
+struct Scope<'s, 'a> {
+    current: &'s Item<'a>,
+    last: Option<usize>,
+    lenient: bool,
+}
+
+impl JsonPath {
+    /// The items `jsonb_path_query` would return for `document`, in the
+    /// same order, or `None` where evaluation fails.
+    fn evaluate<'a>(&self, document: &'a OrderedJson) -> Option<Vec<Item<'a>>> {
+        let root = Item::Node(String::new(), document);
+        let evaluator = Evaluator {
+            strict: self.strict,
+            root: &root,
+        };
+        let scope = Scope {
+            current: &root,
+            last: None,
+            lenient: false,
+        };
+        if self.expr.is_predicate() {
+            let result = match evaluator.predicate(&self.expr, scope) {
+                Truth::True => OrderedJson::Bool(true),
+                Truth::False => OrderedJson::Bool(false),
+                Truth::Unknown => OrderedJson::Null,
+            };
+            return Some(vec![Item::Computed(result)]);
+        }
+        evaluator.eval(&self.expr, scope).ok()
+    }
+}
+
+impl Expr {
+    fn is_predicate(&self) -> bool {
+        matches!(
+            self,
+            Expr::Compare(..)
+                | Expr::StartsWith(..)
+                | Expr::Exists(_)
+                | Expr::And(..)
+                | Expr::Or(..)
+                | Expr::Not(_)
+                | Expr::IsUnknown(_)
+        )
+    }
+
+    /// Whether this accessor chain passes through `.**`.
+    fn has_descendants(&self) -> bool {
+        match self {
+            Expr::Access(base, accessor) => {
+                matches!(accessor, Accessor::Descendants(..)) || base.has_descendants()
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Evaluator<'r, 'a> {
+    strict: bool,
+    root: &'r Item<'a>,
+}
+
+impl<'r, 'a> Evaluator<'r, 'a> {
+    fn eval(&self, expr: &Expr, scope: Scope<'_, 'a>) -> Eval<Vec<Item<'a>>> {
+        match expr {
+            Expr::Root => Ok(vec![self.root.clone()]),
+            Expr::Current => Ok(vec![scope.current.clone()]),
+            Expr::Last => {
+                let last = scope.last.ok_or(EvalError)?;
+                Ok(vec![Item::Computed(OrderedJson::Number(
+                    (last as i64 - 1).into(),
+                ))])
+            }
+            Expr::Literal(value) => Ok(vec![Item::Computed(value.clone())]),
+            Expr::Access(base, accessor) => {
+                let scope = Scope {
+                    lenient: scope.lenient || base.has_descendants(),
+                    ..scope
+                };
+                let mut out = Vec::new();
+                for item in self.eval(base, scope)? {
+                    self.access(accessor, &item, !self.strict, scope, &mut out)?;
+                }
+                Ok(out)
+            }
+            Expr::Negate(arg) | Expr::Plus(arg) => {
+                let negate = matches!(expr, Expr::Negate(_));
+                self.unwrapped(self.eval(arg, scope)?)
+                    .iter()
+                    .map(|item| {
+                        let x = number(item)?;
+                        computed_number(if negate { -x } else { x })
+                    })
+                    .collect()
+            }
+            Expr::Arithmetic(left, op, right) => {
+                let left = self.single_number(left, scope)?;
+                let right = self.single_number(right, scope)?;
+                let result = match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' if right != 0.0 => left / right,
+                    '%' if right != 0.0 => left % right,
+                    _ => return Err(EvalError),
+                };
+                Ok(vec![computed_number(result)?])
+            }
+            predicate => {
+                let result = match self.predicate(predicate, scope) {
+                    Truth::True => OrderedJson::Bool(true),
+                    Truth::False => OrderedJson::Bool(false),
+                    Truth::Unknown => OrderedJson::Null,
+                };
+                Ok(vec![Item::Computed(result)])
+            }
+        }
+    }
+
+    /// Applies one accessor to `item`. In lax mode most accessors apply to
+    /// the elements of an array instead, one level deep.
+    fn access(
+        &self,
+        accessor: &Accessor,
+        item: &Item<'a>,
+        unwrap: bool,
+        scope: Scope<'_, 'a>,
+        out: &mut Vec<Item<'a>>,
+    ) -> Eval<()> {
+        let unwraps = matches!(
+            accessor,
+            Accessor::Member(_)
+                | Accessor::AnyMember
+                | Accessor::Filter(_)
+                | Accessor::Method(Method::Double | Method::Ceiling | Method::Floor | Method::Abs)
+        );
+        if unwrap && unwraps && item.is_array() {
+            for element in item.elements() {
+                self.access(accessor, &element, false, scope, out)?;
+            }
+            return Ok(());
+        }
+
+        let structural = || {
+            if self.strict && !scope.lenient {
+                Err(EvalError)
+            } else {
+                Ok(())
+            }
+        };
+        match accessor {
+            Accessor::Member(key) => match item.member(key) {
+                Some(child) => out.push(child),
+                None => structural()?,
+            },
+            Accessor::AnyMember => match item.value() {
+                OrderedJson::Object(_) => out.extend(item.members()),
+                _ => structural()?,
+            },
+            Accessor::AnyElement if item.is_array() => out.extend(item.elements()),
+            Accessor::AnyElement if !self.strict => out.push(item.clone()),
+            Accessor::AnyElement => structural()?,
+            Accessor::Elements(subscripts) => {
+                let elements = if item.is_array() {
+                    item.elements()
+                } else if !self.strict {
+                    vec![item.clone()]
+                } else {
+                    return structural();
+                };
+                let inner = Scope {
+                    last: Some(elements.len()),
+                    ..scope
+                };
+                for (from, to) in subscripts {
+                    let from = self.index(from, inner)?;
+                    let to = match to {
+                        Some(to) => self.index(to, inner)?,
+                        None => from,
+                    };
+                    if from < 0 || from > to || to >= elements.len() as i64 {
+                        structural()?;
+                    }
+                    let from = from.max(0);
+                    let to = to.min(elements.len() as i64 - 1);
+                    for index in from..=to {
+                        out.push(elements[index as usize].clone());
+                    }
+                }
+            }
+            Accessor::Descendants(first, last) => {
+                if *first == 0 {
+                    out.push(item.clone());
+                }
+                descendants(item, 1, *first, *last, out);
+            }
+            Accessor::Filter(predicate) => {
+                let inner = Scope {
+                    current: item,
+                    ..scope
+                };
+                if self.predicate(predicate, inner) == Truth::True {
+                    out.push(item.clone());
+                }
+            }
+            Accessor::Method(method) => {
+                if let Some(result) = self.method(*method, item, structural)? {
+                    out.push(Item::Computed(result));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn method(
+        &self,
+        method: Method,
+        item: &Item<'a>,
+        structural: impl Fn() -> Eval<()>,
+    ) -> Eval<Option<OrderedJson>> {
+        let value = item.value();
+        let result = match method {
+            Method::Type => OrderedJson::String(
+                match value {
+                    OrderedJson::Null => "null",
+                    OrderedJson::Bool(_) => "boolean",
+                    OrderedJson::Number(_) => "number",
+                    OrderedJson::String(_) => "string",
+                    OrderedJson::Array(_) => "array",
+                    OrderedJson::Object(_) => "object",
+                }
+                .to_string(),
+            ),
+            Method::Size => match value {
+                OrderedJson::Array(items) => OrderedJson::Number(items.len().into()),
+                _ if !self.strict => OrderedJson::Number(1.into()),
+                _ => return structural().map(|()| None),
+            },
+            Method::Double => {
+                let x = match value {
+                    OrderedJson::Number(n) => n.as_f64().ok_or(EvalError)?,
+                    OrderedJson::String(s) => s.trim().parse().map_err(|_| EvalError)?,
+                    _ => return Err(EvalError),
+                };
+                computed_value(x)?
+            }
+            Method::Ceiling => computed_value(number(item)?.ceil())?,
+            Method::Floor => computed_value(number(item)?.floor())?,
+            Method::Abs => computed_value(number(item)?.abs())?,
+        };
+        Ok(Some(result))
+    }
+
+    /// An array subscript: a single number, truncated toward zero.
+    fn index(&self, expr: &Expr, scope: Scope<'_, 'a>) -> Eval<i64> {
+        match self.eval(expr, scope)?.as_slice() {
+            [item] => {
+                let x = number(item)?.trunc();
+                if x.abs() > i32::MAX as f64 {
+                    return Err(EvalError);
+                }
+                Ok(x as i64)
+            }
+            _ => Err(EvalError),
+        }
+    }
+
+    fn single_number(&self, expr: &Expr, scope: Scope<'_, 'a>) -> Eval<f64> {
+        match self.unwrapped(self.eval(expr, scope)?).as_slice() {
+            [item] => number(item),
+            _ => Err(EvalError),
+        }
+    }
+
+    /// In lax mode, arrays among `items` stand for their elements.
+    fn unwrapped(&self, items: Vec<Item<'a>>) -> Vec<Item<'a>> {
+        if self.strict {
+            return items;
+        }
+        items
+            .into_iter()
+            .flat_map(|item| {
+                if item.is_array() {
+                    item.elements()
+                } else {
+                    vec![item]
+                }
+            })
+            .collect()
+    }
+
+    fn predicate(&self, expr: &Expr, scope: Scope<'_, 'a>) -> Truth {
+        match expr {
+            Expr::Compare(left, op, right) => {
+                self.any_pair(left, right, true, scope, |a, b| compare(*op, a, b))
+            }
+            Expr::StartsWith(whole, initial) => {
+                self.any_pair(whole, initial, false, scope, |a, b| match (a, b) {
+                    (OrderedJson::String(a), OrderedJson::String(b)) => {
+                        a.starts_with(b.as_str()).into()
+                    }
+                    _ => Truth::Unknown,
+                })
+            }
+            Expr::Exists(arg) => match self.eval(arg, scope) {
+                Ok(items) => (!items.is_empty()).into(),
+                Err(EvalError) => Truth::Unknown,
+            },
+            Expr::And(left, right) => match self.predicate(left, scope) {
+                Truth::False => Truth::False,
+                left => match (left, self.predicate(right, scope)) {
+                    (_, Truth::False) => Truth::False,
+                    (Truth::True, Truth::True) => Truth::True,
+                    _ => Truth::Unknown,
+                },
+            },
+            Expr::Or(left, right) => match self.predicate(left, scope) {
+                Truth::True => Truth::True,
+                left => match (left, self.predicate(right, scope)) {
+                    (_, Truth::True) => Truth::True,
+                    (Truth::False, Truth::False) => Truth::False,
+                    _ => Truth::Unknown,
+                },
+            },
+            Expr::Not(arg) => match self.predicate(arg, scope) {
+                Truth::True => Truth::False,
+                Truth::False => Truth::True,
+                Truth::Unknown => Truth::Unknown,
+            },
+            Expr::IsUnknown(arg) => (self.predicate(arg, scope) == Truth::Unknown).into(),
+            _ => Truth::Unknown,
+        }
+    }
+
+    /// Compares every pair of items from the two sides: true if any pair
+    /// holds, unknown if a pair cannot be compared (in strict mode, if any
+    /// pair cannot).
+    fn any_pair(
+        &self,
+        left: &Expr,
+        right: &Expr,
+        unwrap_right: bool,
+        scope: Scope<'_, 'a>,
+        test: impl Fn(&OrderedJson, &OrderedJson) -> Truth,
+    ) -> Truth {
+        let Ok(left) = self.eval(left, scope) else {
+            return Truth::Unknown;
+        };
+        let Ok(mut right) = self.eval(right, scope) else {
+            return Truth::Unknown;
+        };
+        if unwrap_right {
+            right = self.unwrapped(right);
+        }
+        let mut found = false;
+        let mut unknown = false;
+        for left in self.unwrapped(left) {
+            for right in &right {
+                match test(left.value(), right.value()) {
+                    Truth::True if !self.strict => return Truth::True,
+                    Truth::True => found = true,
+                    Truth::Unknown if self.strict => return Truth::Unknown,
+                    Truth::Unknown => unknown = true,
+                    Truth::False => {}
+                }
+            }
+        }
+        if found {
+            Truth::True
+        } else if unknown {
+            Truth::Unknown
+        } else {
+            Truth::False
+        }
+    }
+}
+
+/// The descendants `.**{first to last}` selects below `item`, in document
+/// order, each before its own children. `{last}` selects leaves only.
+fn descendants<'a>(item: &Item<'a>, level: u32, first: u32, last: u32, out: &mut Vec<Item<'a>>) {
+    let children = match item.value() {
+        OrderedJson::Object(_) => item.members(),
+        OrderedJson::Array(_) => item.elements(),
+        _ => return,
+    };
+    for child in children {
+        let container = matches!(
+            child.value(),
+            OrderedJson::Object(_) | OrderedJson::Array(_)
+        );
+        if level >= first || (first == u32::MAX && last == u32::MAX && !container) {
+            out.push(child.clone());
+        }
+        if level < last && container {
+            descendants(&child, level + 1, first, last, out);
+        }
+    }
+}
+
+fn compare(op: CompareOp, left: &OrderedJson, right: &OrderedJson) -> Truth {
+    let ordering = match (left, right) {
+        (OrderedJson::Null, OrderedJson::Null) => Ordering::Equal,
+        // Nulls are unequal to, but not ordered against, everything else.
+        (OrderedJson::Null, _) | (_, OrderedJson::Null) => return (op == CompareOp::Ne).into(),
+        (OrderedJson::Bool(a), OrderedJson::Bool(b)) => a.cmp(b),
+        (OrderedJson::Number(a), OrderedJson::Number(b)) => match compare_numbers(a, b) {
+            Some(ordering) => ordering,
+            None => return Truth::Unknown,
+        },
+        (OrderedJson::String(a), OrderedJson::String(b)) => a.cmp(b),
+        _ => return Truth::Unknown,
+    };
+    match op {
+        CompareOp::Eq => ordering.is_eq(),
+        CompareOp::Ne => ordering.is_ne(),
+        CompareOp::Lt => ordering.is_lt(),
+        CompareOp::Le => ordering.is_le(),
+        CompareOp::Gt => ordering.is_gt(),
+        CompareOp::Ge => ordering.is_ge(),
+    }
+    .into()
+}
+
+fn number(item: &Item<'_>) -> Eval<f64> {
+    match item.value() {
+        OrderedJson::Number(n) => n.as_f64().ok_or(EvalError),
+        _ => Err(EvalError),
+    }
+}
+
+fn computed_value(x: f64) -> Eval<OrderedJson> {
+    Number::from_f64(x)
+        .map(OrderedJson::Number)
+        .ok_or(EvalError)
+}
+
+fn computed_number<'a>(x: f64) -> Eval<Item<'a>> {
+    computed_value(x).map(Item::Computed)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(&'static str),
+    Word(String),
+    Str(String),
+    Num(String),
+    Var(String),
+}
+
+const PUNCTUATION: [&str; 27] = [
+    "**", "==", "!=", "<>", "<=", ">=", "&&", "||", "$", "@", ".", "*", "[", "]", "{", "}", "(",
+    ")", ",", "?", "+", "-", "/", "%", "<", ">", "!",
+];
+
+/// Characters that end a bare key or keyword.
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || "?%$.[]{}()|&!=<>@#,*:-+/\\\"'".contains(c)
+}
+
+fn tokenize(path: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = path.trim_start();
+    while !rest.is_empty() {
+        let after_value = matches!(
+            tokens.last(),
+            Some(
+                Token::Word(_)
+                    | Token::Str(_)
+                    | Token::Num(_)
+                    | Token::Var(_)
+                    | Token::Punct("$" | "@" | "*" | "**" | ")" | "]" | "}")
+            )
+        );
+        let starts_number = rest.starts_with(|c: char| c.is_ascii_digit())
+            || (!after_value
+                && rest.starts_with('.')
+                && rest[1..].starts_with(|c: char| c.is_ascii_digit()));
+        let (token, len) = if starts_number {
+            let len = number_len(rest);
+            (Token::Num(rest[..len].to_string()), len)
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            let (text, len) = string_literal(quoted)?;
+            (Token::Str(text), len + 1)
+        } else if let Some(name) = rest
+            .strip_prefix('$')
+            .filter(|name| name.starts_with(|c: char| !is_special(c) || c == '"'))
+        {
+            let (name, len) = match name.strip_prefix('"') {
+                Some(quoted) => string_literal(quoted).map(|(name, len)| (name, len + 1))?,
+                None => {
+                    let len = name.find(is_special).unwrap_or(name.len());
+                    (name[..len].to_string(), len)
+                }
+            };
+            (Token::Var(name), len + 1)
+        } else if let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+            let punct = if *punct == "<>" { "!=" } else { punct };
+            (Token::Punct(punct), punct.len())
+        } else {
+            let len = rest.find(is_special).unwrap_or(rest.len());
+            if len == 0 {
+                return None;
+            }
+            (Token::Word(rest[..len].to_string()), len)
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    Some(tokens)
+}
+
+/// Length of the number at the start of `text`: digits, an optional
+/// fraction and an optional exponent.
+fn number_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let digits = |from: usize| {
+        from + bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut len = digits(0);
+    if bytes.get(len) == Some(&b'.') && bytes.get(len + 1).is_some_and(u8::is_ascii_digit) {
+        len = digits(len + 1);
+    }
+    if matches!(bytes.get(len), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+        if bytes.get(len + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            len = digits(len + 1 + sign);
+        }
+    }
+    len
+}
+
+/// Decodes a string literal whose opening quote has been consumed, returning
+/// its value and the length up to and including the closing quote.
+fn string_literal(text: &str) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut units = Vec::new();
+    let mut chars = text.char_indices();
+    while let Some((at, c)) = chars.next() {
+        if c != '\\' && !units.is_empty() {
+            value.push_str(&String::from_utf16(&units).ok()?);
+            units.clear();
+        }
+        match c {
+            '"' => return Some((value, at + 1)),
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                if escaped == 'u' {
+                    let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    units.push(u16::from_str_radix(&hex, 16).ok()?);
+                    continue;
+                }
+                if !units.is_empty() {
+                    value.push_str(&String::from_utf16(&units).ok()?);
+                    units.clear();
+                }
+                value.push(match escaped {
+                    'b' => '\u{08}',
+                    'f' => '\u{0C}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'v' => '\u{0B}',
+                    'x' => {
+                        let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    other => other,
+                });
+            }
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl JsonPath {
+    /// Parses the subset of SQL/JSON path syntax the evaluator supports, or
+    /// returns `None` (for variables, `like_regex`, `.datetime()`, ...).
+    fn parse(path: &str) -> Option<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(path)?,
+            pos: 0,
+        };
+        let strict = parser.eat_word("strict");
+        if !strict {
+            parser.eat_word("lax");
+        }
+        let expr = parser.or()?;
+        (parser.pos == parser.tokens.len()).then_some(JsonPath { strict, expr })
+    }
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some(Token::Punct(p)) if *p == punct);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some(Token::Word(w)) if w == word);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Option<()> {
+        self.eat(punct).then_some(())
+    }
+
+    fn or(&mut self) -> Option<Expr> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Some(left)
+    }
+
+    fn and(&mut self) -> Option<Expr> {
+        let mut left = self.not()?;
+        while self.eat("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Some(left)
+    }
+
+    fn not(&mut self) -> Option<Expr> {
+        if self.eat("!") {
+            return Some(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Option<Expr> {
+        let left = self.additive()?;
+        let op = match self.tokens.get(self.pos).cloned() {
+            Some(Token::Punct("==")) => CompareOp::Eq,
+            Some(Token::Punct("!=")) => CompareOp::Ne,
+            Some(Token::Punct("<")) => CompareOp::Lt,
+            Some(Token::Punct("<=")) => CompareOp::Le,
+            Some(Token::Punct(">")) => CompareOp::Gt,
+            Some(Token::Punct(">=")) => CompareOp::Ge,
+            _ if self.eat_word("starts") => {
+                self.eat_word("with").then_some(())?;
+                let initial = self.additive()?;
+                return Some(Expr::StartsWith(Box::new(left), Box::new(initial)));
+            }
+            _ => return Some(left),
+        };
+        self.pos += 1;
+        let right = self.additive()?;
+        Some(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn additive(&mut self) -> Option<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                '+'
+            } else if self.eat("-") {
+                '-'
+            } else {
+                return Some(left);
+            };
+            left = Expr::Arithmetic(Box::new(left), op, Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Option<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                '*'
+            } else if self.eat("/") {
+                '/'
+            } else if self.eat("%") {
+                '%'
+            } else {
+                return Some(left);
+            };
+            left = Expr::Arithmetic(Box::new(left), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        if self.eat("-") {
+            return Some(Expr::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return Some(Expr::Plus(Box::new(self.unary()?)));
+        }
+        self.accessors()
+    }
+
+    fn accessors(&mut self) -> Option<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            let accessor = if self.eat(".") {
+                self.dot_accessor()?
+            } else if self.eat("[") {
+                self.subscripts()?
+            } else if self.eat("?") {
+                self.expect("(")?;
+                let predicate = self.or()?;
+                self.expect(")")?;
+                Accessor::Filter(Box::new(predicate))
+            } else {
+                return Some(expr);
+            };
+            expr = Expr::Access(Box::new(expr), accessor);
+        }
+    }
+
+    fn dot_accessor(&mut self) -> Option<Accessor> {
+        match self.next()? {
+            Token::Punct("*") => Some(Accessor::AnyMember),
+            Token::Punct("**") => {
+                if !self.eat("{") {
+                    return Some(Accessor::Descendants(0, u32::MAX));
+                }
+                let first = self.level()?;
+                let last = if self.eat_word("to") {
+                    self.level()?
+                } else {
+                    first
+                };
+                self.expect("}")?;
+                Some(Accessor::Descendants(first, last))
+            }
+            Token::Str(key) => Some(Accessor::Member(key)),
+            Token::Word(name) if self.eat("(") => {
+                self.expect(")")?;
+                let method = match name.as_str() {
+                    "type" => Method::Type,
+                    "size" => Method::Size,
+                    "double" => Method::Double,
+                    "ceiling" => Method::Ceiling,
+                    "floor" => Method::Floor,
+                    "abs" => Method::Abs,
+                    _ => return None,
+                };
+                Some(Accessor::Method(method))
+            }
+            Token::Word(key) => Some(Accessor::Member(key)),
+            _ => None,
+        }
+    }
+
+    fn level(&mut self) -> Option<u32> {
+        match self.next()? {
+            Token::Num(digits) => digits.parse().ok(),
+            Token::Word(word) if word == "last" => Some(u32::MAX),
+            _ => None,
+        }
+    }
+
+    fn subscripts(&mut self) -> Option<Accessor> {
+        if self.eat("*") {
+            self.expect("]")?;
+            return Some(Accessor::AnyElement);
+        }
+        let mut subscripts = Vec::new();
+        loop {
+            let from = self.additive()?;
+            let to = if self.eat_word("to") {
+                Some(self.additive()?)
+            } else {
+                None
+            };
+            subscripts.push((from, to));
+            if self.eat("]") {
+                return Some(Accessor::Elements(subscripts));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> Option<Expr> {
+        match self.next()? {
+            Token::Punct("$") => Some(Expr::Root),
+            Token::Punct("@") => Some(Expr::Current),
+            Token::Punct("(") => {
+                let inner = self.or()?;
+                self.expect(")")?;
+                if self.eat_word("is") {
+                    self.eat_word("unknown").then_some(())?;
+                    return Some(Expr::IsUnknown(Box::new(inner)));
+                }
+                Some(inner)
+            }
+            Token::Str(text) => Some(Expr::Literal(OrderedJson::String(text))),
+            Token::Num(text) => {
+                let text = match text.strip_prefix('.') {
+                    Some(fraction) => format!("0.{}", fraction),
+                    None => text,
+                };
+                Some(Expr::Literal(OrderedJson::Number(text.parse().ok()?)))
+            }
+            Token::Word(word) => match word.as_str() {
+                "true" => Some(Expr::Literal(OrderedJson::Bool(true))),
+                "false" => Some(Expr::Literal(OrderedJson::Bool(false))),
+                "null" => Some(Expr::Literal(OrderedJson::Null)),
+                "last" => Some(Expr::Last),
+                "exists" => {
+                    self.expect("(")?;
+                    let arg = self.additive()?;
+                    self.expect(")")?;
+                    Some(Expr::Exists(Box::new(arg)))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MOVIES: &str = r#"{"released": 2014, "movies": [
+        {"title": "A", "year": 2014, "tags": ["x", "y"]},
+        {"year": 2010, "title": "B", "k": {"k": 1}}
+    ], "k": 0}"#;
+
+    /// The pointer of each item `path` yields on [`MOVIES`], or `=` and the
+    /// value for computed items; `None` if the path errors or does not parse.
+    fn trace(path: &str) -> Option<Vec<String>> {
+        let document: OrderedJson = MOVIES.parse().unwrap();
+        let items = JsonPath::parse(path)?.evaluate(&document)?;
+        Some(
+            items
+                .iter()
+                .map(|item| match item {
+                    Item::Node(pointer, _) => pointer.clone(),
+                    Item::Computed(value) => format!("={}", value),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn matches_are_traced_to_their_own_location() {
+        // Both `/released` and `/movies/0/year` hold 2014; only the latter
+        // was selected.
+        assert_eq!(
+            trace(r#"$.movies[*] ? (@.title == "A").year"#).unwrap(),
+            ["/movies/0/year"]
+        );
+        assert_eq!(
+            trace("$.movies.title").unwrap(),
+            ["/movies/0/title", "/movies/1/title"]
+        );
+        assert_eq!(trace("$.movies[last].year").unwrap(), ["/movies/1/year"]);
+        assert_eq!(
+            trace("$.movies[1, 0 to last].year").unwrap(),
+            ["/movies/1/year", "/movies/0/year", "/movies/1/year"]
+        );
+        assert_eq!(
+            trace("$.movies[0].tags[*] ? (@ starts with \"y\")").unwrap(),
+            ["/movies/0/tags/1"]
+        );
+    }
+
+    #[test]
+    fn wildcards_follow_jsonb_key_order() {
+        assert_eq!(trace("$.*").unwrap(), ["/k", "/movies", "/released"]);
+    }
+
+    #[test]
+    fn descendants_repeat_like_postgres() {
+        // In lax mode the `.k` after `.**` also unwraps the `movies` array,
+        // so `/movies/1/k` is reached twice.
+        assert_eq!(
+            trace("$.**.k").unwrap(),
+            ["/k", "/movies/1/k", "/movies/1/k", "/movies/1/k/k"]
+        );
+        assert_eq!(
+            trace("strict $.**.k").unwrap(),
+            ["/k", "/movies/1/k", "/movies/1/k/k"]
+        );
+        assert_eq!(trace("$.**{2}.k").unwrap(), ["/movies/1/k"]);
+    }
+
+    #[test]
+    fn computed_items_have_no_pointer() {
+        assert_eq!(trace("$.movies.size()").unwrap(), ["=2"]);
+        assert_eq!(trace("$.movies[0].year.type()").unwrap(), [r#"="number""#]);
+    }
+
+    #[test]
+    fn errors_and_unsupported_syntax_are_not_traced() {
+        assert_eq!(trace("strict $.missing"), None);
+        assert_eq!(trace("lax $.missing").unwrap(), Vec::<String>::new());
+        assert_eq!(trace(r#"$.movies ? (@.title like_regex "^A")"#), None);
+        assert_eq!(trace("$.movies[*] ? (@.year > $min)"), None);
+    }
+
+    #[test]
+    fn matches_compare_values_not_key_order() {
+        let node: OrderedJson = r#"{"title": "Tenet", "year": 2020, "cast": ["JD", null]}"#
+            .parse()
+            .unwrap();
+        assert!(same_value(
+            &node,
+            &json!({"cast": ["JD", null], "year": 2020, "title": "Tenet"})
+        ));
+        assert!(!same_value(&node, &json!({"year": 2020, "title": "Tenet"})));
+        assert!(!same_value(
+            &node,
+            &json!({"year": "2020", "cast": ["JD", null], "title": "Tenet"})
+        ));
+        assert!(!same_value(
+            &node,
+            &json!({"year": 2020, "cast": [null, "JD"], "title": "Tenet"})
+        ));
+    }
+
+    #[test]
+    fn numbers_match_whatever_their_spelling() {
+        let node: OrderedJson = "[1.50, 1e2, 12345678901234567890]".parse().unwrap();
+        assert!(same_value(
+            &node,
+            &serde_json::from_str("[1.5, 100, 12345678901234567890]").unwrap()
+        ));
+        assert!(!same_value(
+            &node,
+            &serde_json::from_str("[1.5, 100, 12345678901234567891]").unwrap()
+        ));
+    }
+}
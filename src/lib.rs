@@ -0,0 +1,48 @@
+pub mod audit;
+pub mod backend;
+pub mod bulk;
+pub mod document;
+pub mod duplicates;
+pub mod edit;
+pub mod enforce;
+pub mod error;
+pub mod fidelity;
+pub mod ingest;
+pub mod jcs;
+pub mod jsonb;
+pub mod jsonpath;
+pub mod manifest;
+pub mod memory;
+pub mod migrate;
+pub mod ordered;
+pub mod patch;
+pub mod query;
+pub mod reorder;
+pub mod schema;
+pub mod span;
+pub mod sqlite;
+pub mod store;
+pub mod strategy;
+pub mod verify;
+
+pub use backend::JsonStore;
+pub use document::OrderedDocument;
+pub use duplicates::DuplicateKeys;
+pub use edit::LosslessJson;
+pub use enforce::Enforcement;
+pub use error::{Result, StoreError};
+pub use fidelity::FidelityReport;
+pub use json_order_derive::{KeyOrder, OrderedDocument};
+pub use jsonpath::JsonPathMatch;
+pub use manifest::KeyOrderManifest;
+pub use memory::MemoryStore;
+pub use ordered::OrderedJson;
+pub use patch::{Patch, PatchOperation, PatchTarget};
+pub use query::{Direction, DocumentQuery, Filter};
+pub use reorder::{reorder_by_schema, reorder_by_template};
+pub use schema::{KeyOrder, OrderingSchema, SchemaRegistry, UnknownKeys};
+pub use span::SpannedJson;
+pub use sqlite::SqliteStore;
+pub use store::{read_json_file, read_json_file_with, Fragment, OrderedJsonStore, StoredJson};
+pub use strategy::StorageStrategy;
+pub use verify::{verify, NumberDifference, VerificationReport};
@@ -0,0 +1,158 @@
+use crate::jsonb;
+use crate::ordered::{join_pointer, OrderedJson, OrderedMap};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The original key sequence of every object whose order `jsonb` would not
+/// reproduce, keyed by the object's JSON Pointer.
+///
+/// Objects whose keys already come out of `jsonb` in the original order
+/// (including all objects with fewer than two keys) are omitted, so for many
+/// documents the manifest is much smaller than a second copy of the text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyOrderManifest {
+    objects: BTreeMap<String, Vec<String>>,
+}
+
+impl KeyOrderManifest {
+    pub fn from_document(document: &OrderedJson) -> Self {
+        let mut manifest = Self::default();
+        manifest.collect(document, String::new());
+        manifest
+    }
+
+    fn collect(&mut self, value: &OrderedJson, pointer: String) {
+        match value {
+            OrderedJson::Object(map) => {
+                let original: Vec<&String> = map.keys().collect();
+                if original != jsonb::key_order(map.keys()) {
+                    self.objects
+                        .insert(pointer.clone(), original.into_iter().cloned().collect());
+                }
+                for (key, child) in map {
+                    self.collect(child, join_pointer(&pointer, key));
+                }
+            }
+            OrderedJson::Array(items) => {
+                for (index, child) in items.iter().enumerate() {
+                    self.collect(child, join_pointer(&pointer, &index.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The recorded key sequence of the object at `pointer`, if it differs
+    /// from `jsonb` order.
+    pub fn keys_at(&self, pointer: &str) -> Option<&[String]> {
+        self.objects.get(pointer).map(Vec::as_slice)
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Rebuilds the originally ordered document from a value read back from a
+    /// `jsonb` column.
+    ///
+    /// Keys missing from the manifest (for example because the JSONB copy was
+    /// edited after insertion) follow the recorded keys in `jsonb` order.
+    pub fn apply(&self, value: &Value) -> OrderedJson {
+        self.rebuild(value, String::new())
+    }
+
+    fn rebuild(&self, value: &Value, pointer: String) -> OrderedJson {
+        match value {
+            Value::Object(map) => {
+                let recorded = self.keys_at(&pointer).unwrap_or_default();
+                let mut remaining: Vec<&String> =
+                    map.keys().filter(|key| !recorded.contains(key)).collect();
+                remaining.sort_by(|a, b| jsonb::key_cmp(a, b));
+
+                let mut object = OrderedMap::new();
+                for key in recorded.iter().chain(remaining) {
+                    if let Some(child) = map.get(key) {
+                        let child = self.rebuild(child, join_pointer(&pointer, key));
+                        object.insert(key.clone(), child);
+                    }
+                }
+                OrderedJson::Object(object)
+            }
+            Value::Array(items) => OrderedJson::Array(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, child)| {
+                        self.rebuild(child, join_pointer(&pointer, &index.to_string()))
+                    })
+                    .collect(),
+            ),
+            other => other.clone().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_objects_jsonb_would_reorder_are_recorded() {
+        let document: OrderedJson = r#"{
+            "title": "Inception",
+            "cast": [{"name": "Leo", "id": 1}, {"id": 2, "name": "Tom"}],
+            "meta": {"a": 1},
+            "id": 7
+        }"#
+        .parse()
+        .unwrap();
+        let manifest = KeyOrderManifest::from_document(&document);
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(
+            manifest.keys_at("").unwrap(),
+            ["title", "cast", "meta", "id"]
+        );
+        assert_eq!(manifest.keys_at("/cast/0").unwrap(), ["name", "id"]);
+        assert!(manifest.keys_at("/cast/1").is_none());
+        assert!(manifest.keys_at("/meta").is_none());
+    }
+
+    #[test]
+    fn apply_restores_the_original_order() {
+        let text = r#"{"zeta":{"b":[{"yy":1,"x":2}],"a":null},"alpha":true,"mid":"m"}"#;
+        let document: OrderedJson = text.parse().unwrap();
+        let manifest = KeyOrderManifest::from_document(&document);
+        let jsonb_copy: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(manifest.apply(&jsonb_copy).to_string(), text);
+    }
+
+    #[test]
+    fn unrecorded_keys_follow_in_jsonb_order() {
+        let document: OrderedJson = r#"{"b": 1, "a": 2}"#.parse().unwrap();
+        let manifest = KeyOrderManifest::from_document(&document);
+        let edited = json!({"a": 2, "b": 1, "cc": 3, "d": 4});
+        assert_eq!(
+            manifest.apply(&edited).to_string(),
+            r#"{"b":1,"a":2,"d":4,"cc":3}"#
+        );
+        assert_eq!(manifest.apply(&json!({"a": 2})).to_string(), r#"{"a":2}"#);
+    }
+
+    #[test]
+    fn manifests_serialize_as_a_plain_object() {
+        let document: OrderedJson = r#"{"b": {"d": 1, "c": 2}, "a": 0}"#.parse().unwrap();
+        let manifest = KeyOrderManifest::from_document(&document);
+        let stored = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(stored, json!({"": ["b", "a"], "/b": ["d", "c"]}));
+        let loaded: KeyOrderManifest = serde_json::from_value(stored).unwrap();
+        assert_eq!(loaded, manifest);
+        assert!(KeyOrderManifest::from_document(&"[1, {}]".parse().unwrap()).is_empty());
+    }
+}
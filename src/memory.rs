@@ -0,0 +1,232 @@
+//! A [`JsonStore`] held in memory, for tests that should not need a
+//! database.
+//!
+//! Rows look like the `dual` strategy's: the original text plus a
+//! `data_jsonb` value normalized the way PostgreSQL's `jsonb` would
+//! normalize it ([`jsonb::normalize`]), so tests can assert on exactly what
+//! a real `jsonb` column loses.
+
+use crate::backend::JsonStore;
+use crate::error::{Result, StoreError};
+use crate::jcs;
+use crate::jsonb;
+use crate::ordered::split_pointer;
+use crate::store::StoredJson;
+use crate::strategy::StorageStrategy;
+use crate::verify::semantically_equal;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    /// Row `i` has id `i + 1`.
+    rows: Mutex<Vec<StoredJson>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn rows(&self) -> MutexGuard<'_, Vec<StoredJson>> {
+        // A panic while holding the lock cannot leave a half-written row.
+        self.rows
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl JsonStore for MemoryStore {
+    async fn ensure_table_exists(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn insert_json(&self, json_data: &str) -> Result<i32> {
+        let parsed: Value = serde_json::from_str(json_data)?;
+        let mut rows = self.rows();
+        let next = i64::try_from(rows.len()).map_or(i64::MAX, |len| len.saturating_add(1));
+        let id = i32::try_from(next).map_err(|_| StoreError::IdOutOfRange(next))?;
+        rows.push(StoredJson {
+            id,
+            strategy: StorageStrategy::Dual,
+            data_jsonb: Some(jsonb::normalize(&parsed)),
+            raw_text: Some(json_data.to_string()),
+            key_order: None,
+            content_hash: Some(jcs::content_hash_exact(&parsed)),
+            doc_type: None,
+            schema_ordered: None,
+        });
+        Ok(id)
+    }
+
+    async fn get_json_by_id(&self, id: i32) -> Result<StoredJson> {
+        let index = id
+            .checked_sub(1)
+            .and_then(|index| usize::try_from(index).ok())
+            .ok_or(StoreError::NotFound(id))?;
+        self.rows()
+            .get(index)
+            .cloned()
+            .ok_or(StoreError::NotFound(id))
+    }
+
+    async fn list(&self) -> Result<Vec<StoredJson>> {
+        Ok(self.rows().clone())
+    }
+
+    /// Compares against `data_jsonb`, as the PostgreSQL store does.
+    async fn find_by_pointer(&self, pointer: &str, value: &Value) -> Result<Vec<StoredJson>> {
+        if split_pointer(pointer).is_none() {
+            return Err(StoreError::InvalidPointer(pointer.to_string()));
+        }
+        Ok(self
+            .rows()
+            .iter()
+            .filter(|stored| {
+                stored
+                    .data_jsonb
+                    .as_ref()
+                    .and_then(|data_jsonb| data_jsonb.pointer(pointer))
+                    .is_some_and(|at| semantically_equal(at, value))
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordered::OrderedJson;
+    use crate::verify::verify;
+    use serde_json::json;
+
+    const MOVIE: &str = r#"{"title": "Inception", "year": 2010, "rating": 8.80, "id": 1e2}"#;
+
+    #[tokio::test]
+    async fn rows_keep_the_text_and_a_jsonb_like_value() {
+        let store = MemoryStore::new();
+        let id = store.insert_json(MOVIE).await.unwrap();
+        let stored = store.get_json_by_id(id).await.unwrap();
+
+        assert_eq!(stored.raw_text.as_deref(), Some(MOVIE));
+        assert_eq!(
+            stored.data_jsonb.as_ref().unwrap().to_string(),
+            r#"{"id":100,"year":2010,"title":"Inception","rating":8.80}"#
+        );
+        assert_eq!(
+            stored.original_document().unwrap(),
+            MOVIE.parse::<OrderedJson>().unwrap()
+        );
+        assert_eq!(
+            stored.content_hash.as_deref(),
+            Some(jcs::content_hash_exact_str(MOVIE).unwrap().as_str())
+        );
+        stored.check_content_hash().unwrap();
+    }
+
+    #[tokio::test]
+    async fn order_loss_shows_in_the_jsonb_value_only() {
+        let store = MemoryStore::new();
+        let stored = store
+            .get_json_by_id(store.insert_json(MOVIE).await.unwrap())
+            .await
+            .unwrap();
+        let original: OrderedJson = MOVIE.parse().unwrap();
+
+        let jsonb = verify(
+            &original,
+            &OrderedJson::from(stored.data_jsonb.clone().unwrap()),
+        );
+        assert!(!jsonb.order_preserved());
+        assert!(jsonb.semantically_equal);
+        assert_eq!(
+            jsonb.reordered[0].original,
+            ["title", "year", "rating", "id"]
+        );
+        assert_eq!(
+            jsonb.reordered[0].retrieved,
+            ["id", "year", "title", "rating"]
+        );
+
+        assert!(verify(&original, &stored.original_document().unwrap()).passed());
+        assert_eq!(stored.number_differences().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn duplicate_keys_keep_the_last_value_in_jsonb() {
+        let store = MemoryStore::new();
+        let id = store
+            .insert_json(r#"{"b": 1, "a": 2, "b": 3}"#)
+            .await
+            .unwrap();
+        let stored = store.get_json_by_id(id).await.unwrap();
+        assert_eq!(stored.data_jsonb.unwrap(), json!({"a": 2, "b": 3}));
+        assert_eq!(
+            stored.raw_text.as_deref(),
+            Some(r#"{"b": 1, "a": 2, "b": 3}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn ids_count_from_one_and_list_in_order() {
+        let store = MemoryStore::new();
+        assert_eq!(store.insert_json("[1]").await.unwrap(), 1);
+        assert_eq!(store.insert_json("[2]").await.unwrap(), 2);
+        let ids: Vec<i32> = store
+            .list()
+            .await
+            .unwrap()
+            .iter()
+            .map(|stored| stored.id)
+            .collect();
+        assert_eq!(ids, [1, 2]);
+
+        for missing in [0, -1, 3, i32::MIN] {
+            assert!(matches!(
+                store.get_json_by_id(missing).await,
+                Err(StoreError::NotFound(id)) if id == missing
+            ));
+        }
+        assert!(store.insert_json("[1,]").await.is_err());
+        assert_eq!(store.list().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn find_by_pointer_ignores_number_spelling() {
+        let store = MemoryStore::new();
+        store.insert_json(MOVIE).await.unwrap();
+        store
+            .insert_json(r#"{"title": "Tenet", "year": 2020, "tags": {"2020": [1.0]}}"#)
+            .await
+            .unwrap();
+
+        let ids = |found: Vec<StoredJson>| found.iter().map(|stored| stored.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(store.find_by_pointer("/id", &json!(100)).await.unwrap()),
+            [1]
+        );
+        assert_eq!(
+            ids(store.find_by_pointer("/rating", &json!(8.8)).await.unwrap()),
+            [1]
+        );
+        assert_eq!(
+            ids(store
+                .find_by_pointer("/tags/2020/0", &json!(1))
+                .await
+                .unwrap()),
+            [2]
+        );
+        assert!(store
+            .find_by_pointer("/year", &json!("2010"))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            store.find_by_pointer("year", &json!(2010)).await,
+            Err(StoreError::InvalidPointer(_))
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+//! Embedded, versioned schema migrations tracked in a `schema_version` table.
+
+use crate::error::{Result, StoreError};
+use sqlx::{Executor, PgConnection, PgPool, Row};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+/// All migrations, in ascending version order. Append new steps here; never
+/// edit one that has shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create json_test",
+        up: r#"
+            CREATE TABLE IF NOT EXISTS json_test (
+                id SERIAL PRIMARY KEY,
+                data_jsonb JSONB NOT NULL,
+                raw_text TEXT NOT NULL
+            );
+        "#,
+        down: r#"
+            DROP TABLE IF EXISTS json_test;
+        "#,
+    },
+    Migration {
+        version: 2,
+        name: "create storage strategy tables",
+        up: r#"
+            CREATE TABLE IF NOT EXISTS json_text_test (
+                id SERIAL PRIMARY KEY,
+                raw_text TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS json_json_test (
+                id SERIAL PRIMARY KEY,
+                data_json JSON NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS json_manifest_test (
+                id SERIAL PRIMARY KEY,
+                data_jsonb JSONB NOT NULL,
+                key_order JSONB NOT NULL
+            );
+        "#,
+        down: r#"
+            DROP TABLE IF EXISTS json_text_test;
+            DROP TABLE IF EXISTS json_json_test;
+            DROP TABLE IF EXISTS json_manifest_test;
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "add json_test.content_hash",
+        up: r#"
+            ALTER TABLE json_test ADD COLUMN content_hash TEXT;
+            CREATE INDEX json_test_content_hash_idx ON json_test (content_hash);
+        "#,
+        down: r#"
+            ALTER TABLE json_test DROP COLUMN content_hash;
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "add doc_type columns",
+        up: r#"
+            ALTER TABLE json_test ADD COLUMN doc_type TEXT;
+            ALTER TABLE json_text_test ADD COLUMN doc_type TEXT;
+            ALTER TABLE json_json_test ADD COLUMN doc_type TEXT;
+            ALTER TABLE json_manifest_test ADD COLUMN doc_type TEXT;
+        "#,
+        down: r#"
+            ALTER TABLE json_test DROP COLUMN doc_type;
+            ALTER TABLE json_text_test DROP COLUMN doc_type;
+            ALTER TABLE json_json_test DROP COLUMN doc_type;
+            ALTER TABLE json_manifest_test DROP COLUMN doc_type;
+        "#,
+    },
+];
+
+/// Serializes concurrent migrators on the same database.
+pub(crate) const MIGRATION_LOCK_ID: i64 = 0x6a736f6e5f6f7264;
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+async fn ensure_version_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn version_in(conn: &mut PgConnection) -> Result<i64> {
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version")
+        .fetch_one(conn)
+        .await?;
+    Ok(row.try_get("version")?)
+}
+
+/// The highest applied migration, or 0 for an empty database.
+pub async fn current_version(pool: &PgPool) -> Result<i64> {
+    ensure_version_table(pool).await?;
+    version_in(&mut *pool.acquire().await?).await
+}
+
+/// Applies all pending migrations and returns the resulting version.
+pub async fn run(pool: &PgPool) -> Result<i64> {
+    migrate_to(pool, latest_version()).await
+}
+
+/// Migrates up or down until `target` is the current version. Each step runs
+/// in its own transaction.
+pub async fn migrate_to(pool: &PgPool, target: i64) -> Result<i64> {
+    if target < 0 || (target > 0 && !MIGRATIONS.iter().any(|m| m.version == target)) {
+        return Err(StoreError::UnknownMigration(target));
+    }
+    ensure_version_table(pool).await?;
+
+    loop {
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(MIGRATION_LOCK_ID)
+            .execute(&mut *tx)
+            .await?;
+
+        let current = version_in(&mut tx).await?;
+        if current < target {
+            let migration = MIGRATIONS
+                .iter()
+                .find(|m| m.version > current)
+                .expect("target is a known version above current");
+            tx.execute(migration.up).await?;
+            sqlx::query("INSERT INTO schema_version (version, name) VALUES ($1, $2)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut *tx)
+                .await?;
+        } else if current > target {
+            let migration = MIGRATIONS
+                .iter()
+                .find(|m| m.version == current)
+                .ok_or(StoreError::UnknownMigration(current))?;
+            tx.execute(migration.down).await?;
+            sqlx::query("DELETE FROM schema_version WHERE version = $1")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            tx.commit().await?;
+            return Ok(current);
+        }
+        tx.commit().await?;
+    }
+}
+
+/// Rolls every migration back and re-applies them, leaving empty tables.
+pub async fn reset(pool: &PgPool) -> Result<i64> {
+    migrate_to(pool, 0).await?;
+    run(pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_count_up_from_one() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i64 + 1, "{}", migration.name);
+            assert!(!migration.up.trim().is_empty() && !migration.down.trim().is_empty());
+        }
+        assert_eq!(latest_version(), MIGRATIONS.len() as i64);
+    }
+}
@@ -0,0 +1,648 @@
+use linked_hash_map::LinkedHashMap;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::{Number, Value};
+use std::fmt;
+use std::str::FromStr;
+
+pub type OrderedMap = LinkedHashMap<String, OrderedJson>;
+
+/// A JSON value whose objects keep their keys in insertion order.
+///
+/// Equality is order-sensitive: two objects with the same members in a
+/// different order are not equal.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OrderedJson {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<OrderedJson>),
+    Object(OrderedMap),
+}
+
+impl OrderedJson {
+    pub fn object() -> Self {
+        OrderedJson::Object(LinkedHashMap::new())
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, OrderedJson::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            OrderedJson::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            OrderedJson::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            OrderedJson::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<OrderedJson>> {
+        match self {
+            OrderedJson::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<OrderedJson>> {
+        match self {
+            OrderedJson::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&OrderedMap> {
+        match self {
+            OrderedJson::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut OrderedMap> {
+        match self {
+            OrderedJson::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if this is an object.
+    pub fn get(&self, key: &str) -> Option<&OrderedJson> {
+        self.as_object().and_then(|map| map.get(key))
+    }
+
+    /// Appends `key` to this object, or overwrites it in place if it already
+    /// exists. Returns `self` so fields can be chained in the desired order.
+    ///
+    /// Panics if `self` is not an object.
+    pub fn add(&mut self, key: &str, value: impl Into<OrderedJson>) -> &mut Self {
+        let map = self
+            .as_object_mut()
+            .expect("OrderedJson::add called on a non-object");
+        set_member(map, key.to_string(), value.into());
+        self
+    }
+
+    /// Resolves an RFC 6901 JSON Pointer such as `/movies/0/title`.
+    pub fn pointer(&self, pointer: &str) -> Option<&OrderedJson> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        pointer[1..]
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .try_fold(self, |target, token| match target {
+                OrderedJson::Object(map) => map.get(&token),
+                OrderedJson::Array(items) => parse_index(&token).and_then(|i| items.get(i)),
+                _ => None,
+            })
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut OrderedJson> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        pointer[1..]
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .try_fold(self, |target, token| match target {
+                OrderedJson::Object(map) => map.get_mut(&token),
+                OrderedJson::Array(items) => {
+                    parse_index(&token).and_then(move |i| items.get_mut(i))
+                }
+                _ => None,
+            })
+    }
+
+    pub fn to_string_pretty(&self) -> String {
+        serde_json::to_string_pretty(self).expect("OrderedJson serialization cannot fail")
+    }
+
+    /// Converts any serializable value, keeping the field order its
+    /// `Serialize` impl emits (declaration order for derived structs).
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Self> {
+        serde_json::from_str(&serde_json::to_string(value)?)
+    }
+}
+
+/// Appends `token` to a JSON Pointer, escaping `~` and `/`.
+pub fn join_pointer(parent: &str, token: &str) -> String {
+    format!("{}/{}", parent, token.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Splits an RFC 6901 JSON Pointer into unescaped reference tokens, or
+/// `None` if it is neither empty nor starts with `/`.
+pub fn split_pointer(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    let tokens = pointer.strip_prefix('/')?;
+    Some(
+        tokens
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Sets `key` in `map`, keeping its position if it already exists, as
+/// `serde_json`'s `preserve_order` maps do. (`LinkedHashMap::insert` would
+/// move it to the end.)
+pub fn set_member(map: &mut OrderedMap, key: String, value: OrderedJson) {
+    match map.get_mut(&key) {
+        Some(existing) => *existing = value,
+        None => {
+            map.insert(key, value);
+        }
+    }
+}
+
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() > 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+impl fmt::Display for OrderedJson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = if f.alternate() {
+            serde_json::to_string_pretty(self)
+        } else {
+            serde_json::to_string(self)
+        };
+        f.write_str(&text.map_err(|_| fmt::Error)?)
+    }
+}
+
+impl FromStr for OrderedJson {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+impl Serialize for OrderedJson {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            OrderedJson::Null => serializer.serialize_unit(),
+            OrderedJson::Bool(b) => serializer.serialize_bool(*b),
+            OrderedJson::Number(n) => n.serialize(serializer),
+            OrderedJson::String(s) => serializer.serialize_str(s),
+            OrderedJson::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            OrderedJson::Object(map) => {
+                let mut out = serializer.serialize_map(Some(map.len()))?;
+                for (key, value) in map {
+                    out.serialize_entry(key, value)?;
+                }
+                out.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderedJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(OrderedJsonVisitor)
+    }
+}
+
+/// With `arbitrary_precision`, `serde_json` hands a number to visitors as a
+/// single-entry map under this key, holding the number's exact text.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+struct OrderedJsonVisitor;
+
+impl<'de> Visitor<'de> for OrderedJsonVisitor {
+    type Value = OrderedJson;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<OrderedJson, D::Error> {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Number(v.into()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Number(v.into()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<OrderedJson, E> {
+        Ok(Number::from_f64(v).map_or(OrderedJson::Null, OrderedJson::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::String(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<OrderedJson, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(OrderedJson::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<OrderedJson, A::Error> {
+        let mut map = LinkedHashMap::new();
+        let Some(first) = access.next_key::<String>()? else {
+            return Ok(OrderedJson::Object(map));
+        };
+        if first == NUMBER_TOKEN {
+            let text: String = access.next_value()?;
+            return text
+                .parse()
+                .map(OrderedJson::Number)
+                .map_err(de::Error::custom);
+        }
+        map.insert(first, access.next_value()?);
+        while let Some((key, value)) = access.next_entry::<String, OrderedJson>()? {
+            set_member(&mut map, key, value);
+        }
+        Ok(OrderedJson::Object(map))
+    }
+}
+
+impl From<Value> for OrderedJson {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => OrderedJson::Null,
+            Value::Bool(b) => OrderedJson::Bool(b),
+            Value::Number(n) => OrderedJson::Number(n),
+            Value::String(s) => OrderedJson::String(s),
+            Value::Array(items) => OrderedJson::Array(items.into_iter().map(Into::into).collect()),
+            Value::Object(map) => {
+                OrderedJson::Object(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+impl From<&Value> for OrderedJson {
+    fn from(value: &Value) -> Self {
+        value.clone().into()
+    }
+}
+
+/// Keeps key order: the crate enables serde_json's `preserve_order` feature,
+/// so `Value` objects are insertion-ordered as well.
+impl From<OrderedJson> for Value {
+    fn from(value: OrderedJson) -> Self {
+        match value {
+            OrderedJson::Null => Value::Null,
+            OrderedJson::Bool(b) => Value::Bool(b),
+            OrderedJson::Number(n) => Value::Number(n),
+            OrderedJson::String(s) => Value::String(s),
+            OrderedJson::Array(items) => Value::Array(items.into_iter().map(Into::into).collect()),
+            OrderedJson::Object(map) => {
+                Value::Object(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+impl From<&OrderedJson> for Value {
+    fn from(value: &OrderedJson) -> Self {
+        value.clone().into()
+    }
+}
+
+impl From<bool> for OrderedJson {
+    fn from(b: bool) -> Self {
+        OrderedJson::Bool(b)
+    }
+}
+
+impl From<&str> for OrderedJson {
+    fn from(s: &str) -> Self {
+        OrderedJson::String(s.to_string())
+    }
+}
+
+impl From<String> for OrderedJson {
+    fn from(s: String) -> Self {
+        OrderedJson::String(s)
+    }
+}
+
+impl From<Number> for OrderedJson {
+    fn from(n: Number) -> Self {
+        OrderedJson::Number(n)
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for OrderedJson {
+                fn from(n: $ty) -> Self {
+                    OrderedJson::Number(n.into())
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+impl From<f64> for OrderedJson {
+    fn from(f: f64) -> Self {
+        Number::from_f64(f).map_or(OrderedJson::Null, OrderedJson::Number)
+    }
+}
+
+impl<T: Into<OrderedJson>> From<Option<T>> for OrderedJson {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(OrderedJson::Null, Into::into)
+    }
+}
+
+impl<T: Into<OrderedJson>> From<Vec<T>> for OrderedJson {
+    fn from(items: Vec<T>) -> Self {
+        OrderedJson::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl FromIterator<(String, OrderedJson)> for OrderedJson {
+    fn from_iter<I: IntoIterator<Item = (String, OrderedJson)>>(iter: I) -> Self {
+        OrderedJson::Object(iter.into_iter().collect())
+    }
+}
+
+/// Builds an [`OrderedJson`] with `serde_json::json!` syntax. Object keys keep
+/// the order in which they are written; interpolated expressions may be any
+/// `Serialize` type.
+#[macro_export]
+macro_rules! ordered_json {
+    ($($json:tt)+) => {
+        $crate::ordered_json_internal!($($json)+)
+    };
+}
+
+// Adapted from the tt-muncher behind `serde_json::json!`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! ordered_json_internal {
+    (@array [$($elems:expr,)*]) => {
+        vec![$($elems,)*]
+    };
+    (@array [$($elems:expr),*]) => {
+        vec![$($elems),*]
+    };
+    (@array [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!(null)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!(true)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!(false)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!([$($array)*])] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!({$($map)*})] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!($next),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $last:expr) => {
+        $crate::ordered_json_internal!(@array [$($elems,)* $crate::ordered_json_internal!($last)])
+    };
+    (@array [$($elems:expr),*] , $($rest:tt)*) => {
+        $crate::ordered_json_internal!(@array [$($elems,)*] $($rest)*)
+    };
+    (@array [$($elems:expr),*] $unexpected:tt $($rest:tt)*) => {
+        $crate::ordered_json_unexpected!($unexpected)
+    };
+
+    (@object $object:ident () () ()) => {};
+    (@object $object:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        $crate::ordered::set_member(&mut $object, ($($key)+).into(), $value);
+        $crate::ordered_json_internal!(@object $object () ($($rest)*) ($($rest)*));
+    };
+    (@object $object:ident [$($key:tt)+] ($value:expr) $unexpected:tt $($rest:tt)*) => {
+        $crate::ordered_json_unexpected!($unexpected);
+    };
+    (@object $object:ident [$($key:tt)+] ($value:expr)) => {
+        $crate::ordered::set_member(&mut $object, ($($key)+).into(), $value);
+    };
+    (@object $object:ident ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!(null)) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!(true)) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!(false)) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: [$($array:tt)*] $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!([$($array)*])) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: {$($map:tt)*} $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!({$($map)*})) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!($value)) , $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: $value:expr) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object [$($key)+] ($crate::ordered_json_internal!($value)));
+    };
+    (@object $object:ident ($($key:tt)+) (:) $copy:tt) => {
+        $crate::ordered_json_internal!();
+    };
+    (@object $object:ident ($($key:tt)+) () $copy:tt) => {
+        $crate::ordered_json_internal!();
+    };
+    (@object $object:ident () (: $($rest:tt)*) ($colon:tt $($copy:tt)*)) => {
+        $crate::ordered_json_unexpected!($colon);
+    };
+    (@object $object:ident ($($key:tt)*) (, $($rest:tt)*) ($comma:tt $($copy:tt)*)) => {
+        $crate::ordered_json_unexpected!($comma);
+    };
+    (@object $object:ident () (($key:expr) : $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object ($key) (: $($rest)*) (: $($rest)*));
+    };
+    (@object $object:ident ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
+        $crate::ordered_json_internal!(@object $object ($($key)* $tt) ($($rest)*) ($($rest)*));
+    };
+
+    (null) => {
+        $crate::ordered::OrderedJson::Null
+    };
+    (true) => {
+        $crate::ordered::OrderedJson::Bool(true)
+    };
+    (false) => {
+        $crate::ordered::OrderedJson::Bool(false)
+    };
+    ([]) => {
+        $crate::ordered::OrderedJson::Array(vec![])
+    };
+    ([ $($tt:tt)+ ]) => {
+        $crate::ordered::OrderedJson::Array($crate::ordered_json_internal!(@array [] $($tt)+))
+    };
+    ({}) => {
+        $crate::ordered::OrderedJson::object()
+    };
+    ({ $($tt:tt)+ }) => {
+        $crate::ordered::OrderedJson::Object({
+            let mut object = $crate::ordered::OrderedMap::new();
+            $crate::ordered_json_internal!(@object object () ($($tt)+) ($($tt)+));
+            object
+        })
+    };
+    ($other:expr) => {
+        $crate::ordered::OrderedJson::from_serialize(&$other).unwrap()
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! ordered_json_unexpected {
+    () => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macro_keeps_the_written_key_order() {
+        let year = 2010;
+        let movie = ordered_json!({
+            "title": "Inception",
+            "year": year,
+            "cast": [{"name": "Leonardo", "role": null}, true, 8.8],
+            "a": {}
+        });
+        assert_eq!(
+            movie.to_string(),
+            r#"{"title":"Inception","year":2010,"cast":[{"name":"Leonardo","role":null},true,8.8],"a":{}}"#
+        );
+        let keys: Vec<&String> = movie.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["title", "year", "cast", "a"]);
+    }
+
+    #[test]
+    fn parsing_and_printing_round_trip() {
+        let text = r#"{"z":1,"a":[{"y":null,"b":false}],"m":"x"}"#;
+        let value: OrderedJson = text.parse().unwrap();
+        assert_eq!(value.to_string(), text);
+        assert_eq!(
+            format!("{:#}", value),
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+        assert_eq!(
+            Value::from(value),
+            serde_json::from_str::<Value>(text).unwrap()
+        );
+    }
+
+    #[test]
+    fn equality_depends_on_key_order() {
+        let ab: OrderedJson = r#"{"a": 1, "b": 2}"#.parse().unwrap();
+        let ba: OrderedJson = r#"{"b": 2, "a": 1}"#.parse().unwrap();
+        assert_ne!(ab, ba);
+        assert_eq!(Value::from(ab), Value::from(ba));
+    }
+
+    #[test]
+    fn add_appends_and_chains() {
+        let mut value = OrderedJson::object();
+        value.add("z", 1).add("a", "x").add("m", vec![1, 2]);
+        assert_eq!(value, ordered_json!({"z": 1, "a": "x", "m": [1, 2]}));
+        assert_eq!(value.get("a").and_then(OrderedJson::as_str), Some("x"));
+    }
+
+    #[test]
+    fn pointers_unescape_tokens() {
+        let mut value = ordered_json!({"a/b": {"~c": [10, 11]}, "": 0});
+        assert_eq!(value.pointer("/a~1b/~0c/1"), Some(&OrderedJson::from(11)));
+        assert_eq!(value.pointer("/"), Some(&OrderedJson::from(0)));
+        assert_eq!(value.pointer(""), Some(&value.clone()));
+        assert!(value.pointer("/a~1b/~0c/01").is_none());
+        assert!(value.pointer("/a~1b/~0c/2").is_none());
+        assert!(value.pointer("a").is_none());
+        *value.pointer_mut("/a~1b/~0c/0").unwrap() = OrderedJson::from("ten");
+        assert_eq!(value.to_string(), r#"{"a/b":{"~c":["ten",11]},"":0}"#);
+    }
+
+    #[test]
+    fn serializable_values_keep_their_field_order() {
+        #[derive(serde::Serialize)]
+        struct Movie {
+            title: &'static str,
+            year: u32,
+            director: Option<&'static str>,
+        }
+        let movie = Movie {
+            title: "Tenet",
+            year: 2020,
+            director: None,
+        };
+        assert_eq!(
+            ordered_json!({"movie": movie, "n": 1}).to_string(),
+            r#"{"movie":{"title":"Tenet","year":2020,"director":null},"n":1}"#
+        );
+    }
+
+    #[test]
+    fn setting_a_key_again_keeps_its_position() {
+        let mut value = ordered_json!({"a": 1, "b": 2, "a": 3});
+        assert_eq!(value.to_string(), r#"{"a":3,"b":2}"#);
+        value.add("a", 4);
+        assert_eq!(value.to_string(), r#"{"a":4,"b":2}"#);
+        let parsed: OrderedJson = r#"{"a": 1, "b": 2, "a": 5}"#.parse().unwrap();
+        assert_eq!(parsed.to_string(), r#"{"a":5,"b":2}"#);
+    }
+}
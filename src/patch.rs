@@ -0,0 +1,414 @@
+//! RFC 6902 JSON Patch and RFC 7386 JSON Merge Patch on [`OrderedJson`].
+//!
+//! Replacing a member keeps its slot in the object; members that did not
+//! exist before are appended after the existing ones, in the order the patch
+//! adds them. Array insertions shift later elements as usual.
+//!
+//! Patches apply to any [`PatchTarget`]: an [`OrderedJson`] value, or a
+//! [`LosslessJson`](crate::edit::LosslessJson) text that is edited in place.
+
+use crate::error::{Result, StoreError};
+use crate::ordered::{join_pointer, parse_index, set_member, split_pointer, OrderedJson};
+use crate::verify::semantically_equal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: OrderedJson },
+    Remove { path: String },
+    Replace { path: String, value: OrderedJson },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: OrderedJson },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch {
+    /// RFC 6902: a sequence of operations applied atomically.
+    Json(Vec<PatchOperation>),
+    /// RFC 7386: a partial document; `null` members delete.
+    Merge(OrderedJson),
+}
+
+impl Patch {
+    /// Reads a JSON Patch if `text` is an array, otherwise a merge patch.
+    pub fn parse(text: &str) -> Result<Self> {
+        let patch: OrderedJson = text.parse()?;
+        match patch {
+            OrderedJson::Array(_) => Ok(Patch::Json(serde_json::from_value(patch.into())?)),
+            other => Ok(Patch::Merge(other)),
+        }
+    }
+
+    /// Applies the patch to `target`. On error `target` is left unchanged.
+    pub fn apply<T: PatchTarget + Clone>(&self, target: &mut T) -> Result<()> {
+        match self {
+            Patch::Json(operations) => apply_json_patch(target, operations),
+            Patch::Merge(patch) => {
+                let mut patched = target.clone();
+                merge_patch(&mut patched, "", patch)
+                    .map_err(|message| StoreError::PatchFailed { index: 0, message })?;
+                *target = patched;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A document that patches can be applied to. Paths are JSON Pointers and
+/// errors are human-readable messages.
+pub trait PatchTarget {
+    /// A copy of the value at `path`.
+    fn value_at(&self, path: &str) -> Option<OrderedJson>;
+
+    fn is_object_at(&self, path: &str) -> bool;
+
+    /// RFC 6902 `add`: sets an object member (in place if it exists), inserts
+    /// into an array (`-` appends), or replaces the whole document.
+    fn add(&mut self, path: &str, value: OrderedJson) -> Result<(), String>;
+
+    fn remove(&mut self, path: &str) -> Result<OrderedJson, String>;
+
+    fn replace(&mut self, path: &str, value: OrderedJson) -> Result<(), String>;
+}
+
+/// Applies RFC 6902 `operations` in order, failing with
+/// [`StoreError::PatchTestFailed`] if a `test` does not hold.
+pub fn apply_json_patch<T: PatchTarget + Clone>(
+    target: &mut T,
+    operations: &[PatchOperation],
+) -> Result<()> {
+    let mut patched = target.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, operation).map_err(|error| match error {
+            OperationError::TestFailed(path) => StoreError::PatchTestFailed { index, path },
+            OperationError::Invalid(message) => StoreError::PatchFailed { index, message },
+        })?;
+    }
+    *target = patched;
+    Ok(())
+}
+
+/// Applies an RFC 7386 merge patch to the value at `path`.
+fn merge_patch<T: PatchTarget>(
+    target: &mut T,
+    path: &str,
+    patch: &OrderedJson,
+) -> Result<(), String> {
+    let OrderedJson::Object(members) = patch else {
+        return target.add(path, patch.clone());
+    };
+    if !target.is_object_at(path) {
+        target.add(path, OrderedJson::object())?;
+    }
+    for (key, value) in members {
+        let child = join_pointer(path, key);
+        if value.is_null() {
+            if target.value_at(&child).is_some() {
+                target.remove(&child)?;
+            }
+        } else if target.is_object_at(&child) || value.as_object().is_none() {
+            merge_patch(target, &child, value)?;
+        } else {
+            // A new or non-object member takes the patch with its nulls removed.
+            let mut added = OrderedJson::Null;
+            merge_patch(&mut added, "", value)?;
+            target.add(&child, added)?;
+        }
+    }
+    Ok(())
+}
+
+enum OperationError {
+    TestFailed(String),
+    Invalid(String),
+}
+
+impl From<String> for OperationError {
+    fn from(message: String) -> Self {
+        OperationError::Invalid(message)
+    }
+}
+
+fn apply_operation<T: PatchTarget>(
+    target: &mut T,
+    operation: &PatchOperation,
+) -> Result<(), OperationError> {
+    let missing = |path: &str| format!("no value at {:?}", path);
+    match operation {
+        PatchOperation::Add { path, value } => target.add(path, value.clone())?,
+        PatchOperation::Remove { path } => {
+            target.remove(path)?;
+        }
+        PatchOperation::Replace { path, value } => target.replace(path, value.clone())?,
+        PatchOperation::Move { from, path } => {
+            if path != from && path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move {:?} into its own child {:?}", from, path).into());
+            }
+            let value = target.remove(from)?;
+            target.add(path, value)?;
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = target.value_at(from).ok_or_else(|| missing(from))?;
+            target.add(path, value)?;
+        }
+        PatchOperation::Test { path, value } => {
+            let actual = target.value_at(path).ok_or_else(|| missing(path))?;
+            // Neither member order nor number spelling is significant for
+            // `test`.
+            if !semantically_equal(&Value::from(actual), &Value::from(value)) {
+                return Err(OperationError::TestFailed(path.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl PatchTarget for OrderedJson {
+    fn value_at(&self, path: &str) -> Option<OrderedJson> {
+        self.pointer(path).cloned()
+    }
+
+    fn is_object_at(&self, path: &str) -> bool {
+        self.pointer(path)
+            .is_some_and(|value| value.as_object().is_some())
+    }
+
+    fn add(&mut self, path: &str, value: OrderedJson) -> Result<(), String> {
+        if path.is_empty() {
+            *self = value;
+            return Ok(());
+        }
+        let (parent, last) = split_last(path)?;
+        match resolve_tokens(self, &parent, path)? {
+            OrderedJson::Object(map) => set_member(map, last, value),
+            OrderedJson::Array(items) => {
+                let index = array_insert_index(&last, items.len(), path)?;
+                items.insert(index, value);
+            }
+            _ => return Err(format!("parent of {:?} is not a container", path)),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &str) -> Result<OrderedJson, String> {
+        let (parent, last) = split_last(path)?;
+        let removed = match resolve_tokens(self, &parent, path)? {
+            OrderedJson::Object(map) => map.remove(&last),
+            OrderedJson::Array(items) => parse_index(&last)
+                .filter(|&i| i < items.len())
+                .map(|i| items.remove(i)),
+            _ => None,
+        };
+        removed.ok_or_else(|| format!("no value at {:?}", path))
+    }
+
+    fn replace(&mut self, path: &str, value: OrderedJson) -> Result<(), String> {
+        let tokens =
+            split_pointer(path).ok_or_else(|| format!("invalid JSON Pointer {:?}", path))?;
+        *resolve_tokens(self, &tokens, path)? = value;
+        Ok(())
+    }
+}
+
+/// Splits `path` into the parent's pointer tokens and the last token.
+pub(crate) fn split_last(path: &str) -> Result<(Vec<String>, String), String> {
+    let mut tokens =
+        split_pointer(path).ok_or_else(|| format!("invalid JSON Pointer {:?}", path))?;
+    let last = tokens
+        .pop()
+        .ok_or_else(|| "the whole document cannot be removed".to_string())?;
+    Ok((tokens, last))
+}
+
+/// The position an `add` at array token `last` inserts at.
+pub(crate) fn array_insert_index(last: &str, len: usize, path: &str) -> Result<usize, String> {
+    match last {
+        "-" => Ok(len),
+        token => parse_index(token)
+            .filter(|&i| i <= len)
+            .ok_or_else(|| format!("array index out of bounds at {:?}", path)),
+    }
+}
+
+fn resolve_tokens<'a>(
+    document: &'a mut OrderedJson,
+    tokens: &[String],
+    path: &str,
+) -> Result<&'a mut OrderedJson, String> {
+    tokens
+        .iter()
+        .try_fold(document, |target, token| match target {
+            OrderedJson::Object(map) => map.get_mut(token),
+            OrderedJson::Array(items) => parse_index(token).and_then(move |i| items.get_mut(i)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("no value at {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patched(document: &str, patch: &str) -> Result<String> {
+        let mut document: OrderedJson = document.parse().unwrap();
+        Patch::parse(patch)?.apply(&mut document)?;
+        Ok(document.to_string())
+    }
+
+    #[test]
+    fn rfc6902_examples() {
+        // RFC 6902 Appendix A.
+        let cases = [
+            (
+                r#"{"foo": "bar"}"#,
+                r#"[{"op": "add", "path": "/baz", "value": "qux"}]"#,
+                r#"{"foo":"bar","baz":"qux"}"#,
+            ),
+            (
+                r#"{"foo": ["bar", "baz"]}"#,
+                r#"[{"op": "add", "path": "/foo/1", "value": "qux"}]"#,
+                r#"{"foo":["bar","qux","baz"]}"#,
+            ),
+            (
+                r#"{"baz": "qux", "foo": "bar"}"#,
+                r#"[{"op": "remove", "path": "/baz"}]"#,
+                r#"{"foo":"bar"}"#,
+            ),
+            (
+                r#"{"baz": "qux", "foo": "bar"}"#,
+                r#"[{"op": "replace", "path": "/baz", "value": "boo"}]"#,
+                r#"{"baz":"boo","foo":"bar"}"#,
+            ),
+            (
+                r#"{"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}}"#,
+                r#"[{"op": "move", "from": "/foo/waldo", "path": "/qux/thud"}]"#,
+                r#"{"foo":{"bar":"baz"},"qux":{"corge":"grault","thud":"fred"}}"#,
+            ),
+            (
+                r#"{"foo": ["all", "grass", "cows", "eat"]}"#,
+                r#"[{"op": "move", "from": "/foo/1", "path": "/foo/3"}]"#,
+                r#"{"foo":["all","cows","eat","grass"]}"#,
+            ),
+            (
+                r#"{"foo": "bar"}"#,
+                r#"[{"op": "add", "path": "/child", "value": {"grandchild": {}}}]"#,
+                r#"{"foo":"bar","child":{"grandchild":{}}}"#,
+            ),
+            (
+                r#"{"foo": ["bar"]}"#,
+                r#"[{"op": "add", "path": "/foo/-", "value": ["abc", "def"]}]"#,
+                r#"{"foo":["bar",["abc","def"]]}"#,
+            ),
+            (
+                r#"{"/": 9, "~1": 10}"#,
+                r#"[{"op": "test", "path": "/~01", "value": 10}, {"op": "copy", "from": "/~1", "path": "/c"}]"#,
+                r#"{"/":9,"~1":10,"c":9}"#,
+            ),
+        ];
+        for (document, patch, expected) in cases {
+            assert_eq!(patched(document, patch).unwrap(), expected, "{}", patch);
+        }
+    }
+
+    #[test]
+    fn failed_operations_leave_the_document_unchanged() {
+        let mut document: OrderedJson = r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#.parse().unwrap();
+        let original = document.clone();
+        let failing = [
+            r#"[{"op": "add", "path": "/x", "value": 1}, {"op": "test", "path": "/baz", "value": "bar"}]"#,
+            r#"[{"op": "remove", "path": "/baz"}, {"op": "add", "path": "/baz/bat", "value": "qux"}]"#,
+            r#"[{"op": "add", "path": "/foo/4", "value": 0}]"#,
+            r#"[{"op": "move", "from": "/foo", "path": "/foo/0"}]"#,
+            r#"[{"op": "remove", "path": ""}]"#,
+        ];
+        for patch in failing {
+            assert!(
+                Patch::parse(patch).unwrap().apply(&mut document).is_err(),
+                "{}",
+                patch
+            );
+            assert_eq!(document, original);
+        }
+        match Patch::parse(failing[0]).unwrap().apply(&mut document) {
+            Err(StoreError::PatchTestFailed { index, path }) => {
+                assert_eq!((index, path.as_str()), (1, "/baz"))
+            }
+            other => panic!("expected a failed test, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ignores_key_order() {
+        assert!(patched(
+            r#"{"a": {"x": 1, "y": [1, 2]}}"#,
+            r#"[{"op": "test", "path": "/a", "value": {"y": [1, 2], "x": 1}}]"#
+        )
+        .is_ok());
+        assert!(patched(
+            r#"{"a": [1, 2]}"#,
+            r#"[{"op": "test", "path": "/a", "value": [2, 1]}]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rfc7386_examples() {
+        // RFC 7386 Appendix A.
+        let cases = [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"a":null}"#, r#"{}"#),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+            (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a":["b"]}"#),
+            (
+                r#"{"a":{"b":"c"}}"#,
+                r#"{"a":{"b":"d","c":null}}"#,
+                r#"{"a":{"b":"d"}}"#,
+            ),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+            (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a":"foo"}"#, "null", "null"),
+            (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+            (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+            (
+                r#"{}"#,
+                r#"{"a":{"bb":{"ccc":null}}}"#,
+                r#"{"a":{"bb":{}}}"#,
+            ),
+        ];
+        for (document, patch, expected) in cases {
+            let document: OrderedJson = document.parse().unwrap();
+            let mut target = document.clone();
+            Patch::Merge(patch.parse().unwrap())
+                .apply(&mut target)
+                .unwrap();
+            assert_eq!(target.to_string(), expected, "{} + {}", document, patch);
+        }
+    }
+
+    #[test]
+    fn replaced_members_keep_their_slot() {
+        assert_eq!(
+            patched(
+                r#"{"c": 1, "b": {"y": 1, "x": 2}, "a": 3}"#,
+                r#"{"b": {"x": 0, "z": 4}, "c": 5, "d": 6}"#
+            )
+            .unwrap(),
+            r#"{"c":5,"b":{"y":1,"x":0,"z":4},"a":3,"d":6}"#
+        );
+        assert_eq!(
+            patched(
+                r#"{"c": 1, "b": 2}"#,
+                r#"[{"op": "add", "path": "/c", "value": 9}, {"op": "copy", "from": "/b", "path": "/a"}]"#
+            )
+            .unwrap(),
+            r#"{"c":9,"b":2,"a":2}"#
+        );
+    }
+}
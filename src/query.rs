@@ -0,0 +1,372 @@
+//! Filtering, sorting and paging documents on their `data_jsonb` column while
+//! returning them in their original order.
+//!
+//! Paths are RFC 6901 JSON Pointers and are bound as `text[]` parameters for
+//! PostgreSQL's `#>` operator, so no path or value is ever interpolated into
+//! the SQL text.
+
+use crate::error::{Result, StoreError};
+use crate::ordered::split_pointer;
+use crate::store::{OrderedJsonStore, StoredJson};
+use serde_json::{Number, Value};
+use sqlx::{Postgres, QueryBuilder};
+use std::ops::{Bound, RangeBounds};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// The value at `path` equals `value` (`data_jsonb #> path = value`).
+    Eq { path: String, value: Value },
+    /// The document contains `value` (`data_jsonb @> value`).
+    Contains(Value),
+    /// The last token of the pointer `path` is a key (or string element) of
+    /// its parent (`data_jsonb #> parent ? key`).
+    Exists { path: String },
+    /// The SQL/JSON path expression matches (`data_jsonb @? path`), e.g.
+    /// `$.movies[*] ? (@.year > 2010)`.
+    JsonPath(String),
+    /// The value at `path` is a number within the bounds, compared as exact
+    /// decimals. Documents where it is missing or not a number never match.
+    Range {
+        path: String,
+        lower: Bound<Number>,
+        upper: Bound<Number>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A query over the store's table, built with [`OrderedJsonStore::query`].
+/// Filters are combined with `AND`; rows are ordered by `id` unless
+/// [`order_by`](Self::order_by) says otherwise.
+#[derive(Debug, Clone)]
+pub struct DocumentQuery<'a> {
+    store: &'a OrderedJsonStore,
+    filters: Vec<Filter>,
+    order_by: Vec<(String, Direction)>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl<'a> DocumentQuery<'a> {
+    pub(crate) fn new(store: &'a OrderedJsonStore) -> Self {
+        Self {
+            store,
+            filters: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        }
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn eq(self, path: &str, value: impl Into<Value>) -> Self {
+        self.filter(Filter::Eq {
+            path: path.to_string(),
+            value: value.into(),
+        })
+    }
+
+    pub fn contains(self, value: impl Into<Value>) -> Self {
+        self.filter(Filter::Contains(value.into()))
+    }
+
+    /// Matches documents where the last token of `path` exists in its parent,
+    /// e.g. `exists("/director")`. `path` is checked when the query is built,
+    /// like every other pointer.
+    pub fn exists(self, path: &str) -> Self {
+        self.filter(Filter::Exists {
+            path: path.to_string(),
+        })
+    }
+
+    pub fn jsonpath(self, path: &str) -> Self {
+        self.filter(Filter::JsonPath(path.to_string()))
+    }
+
+    /// Matches documents where the number at `path` lies in `range`, e.g.
+    /// `range("/year", 2000..=2010)`. Bounds are bound as their decimal text
+    /// and cast to `numeric`, so large integers and decimals keep their exact
+    /// value.
+    pub fn range<N: Into<Number> + Clone>(self, path: &str, range: impl RangeBounds<N>) -> Self {
+        let convert = |bound: Bound<&N>| match bound {
+            Bound::Included(n) => Bound::Included(n.clone().into()),
+            Bound::Excluded(n) => Bound::Excluded(n.clone().into()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        self.filter(Filter::Range {
+            path: path.to_string(),
+            lower: convert(range.start_bound()),
+            upper: convert(range.end_bound()),
+        })
+    }
+
+    /// Sorts by the value at `path` using `jsonb` ordering. May be called
+    /// repeatedly; `id` is always the final tie-breaker.
+    pub fn order_by(mut self, path: &str, direction: Direction) -> Self {
+        self.order_by.push((path.to_string(), direction));
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: i64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Runs the query. Documents come back as [`StoredJson`], so
+    /// [`StoredJson::document`] gives the original key order.
+    pub async fn fetch(self) -> Result<Vec<StoredJson>> {
+        let rows = self.build()?.build().fetch_all(self.store.pool()).await?;
+        rows.iter()
+            .map(|row| self.store.stored_from_row(row))
+            .collect()
+    }
+
+    fn build(&self) -> Result<QueryBuilder<'static, Postgres>> {
+        let strategy = self.store.strategy();
+        if !strategy.has_jsonb() {
+            return Err(StoreError::NotQueryable(strategy));
+        }
+
+        let mut builder = QueryBuilder::new(format!(
+            "SELECT {} FROM {}",
+            strategy.columns(),
+            strategy.table()
+        ));
+        for (index, filter) in self.filters.iter().enumerate() {
+            builder.push(if index == 0 { " WHERE " } else { " AND " });
+            push_filter(&mut builder, filter)?;
+        }
+
+        builder.push(" ORDER BY ");
+        for (path, direction) in &self.order_by {
+            builder.push("data_jsonb #> ");
+            builder.push_bind(path_tokens(path)?);
+            builder.push(match direction {
+                Direction::Asc => " ASC, ",
+                Direction::Desc => " DESC, ",
+            });
+        }
+        builder.push("id");
+
+        if let Some(limit) = self.limit {
+            builder.push(" LIMIT ").push_bind(limit);
+        }
+        if let Some(offset) = self.offset {
+            builder.push(" OFFSET ").push_bind(offset);
+        }
+        Ok(builder)
+    }
+}
+
+fn path_tokens(path: &str) -> Result<Vec<String>> {
+    split_pointer(path).ok_or_else(|| StoreError::InvalidPointer(path.to_string()))
+}
+
+fn push_filter(builder: &mut QueryBuilder<'static, Postgres>, filter: &Filter) -> Result<()> {
+    match filter {
+        Filter::Eq { path, value } => {
+            builder.push("data_jsonb #> ").push_bind(path_tokens(path)?);
+            builder.push(" = ").push_bind(value.clone());
+        }
+        Filter::Contains(value) => {
+            builder.push("data_jsonb @> ").push_bind(value.clone());
+        }
+        Filter::Exists { path } => {
+            let mut parent = path_tokens(path)?;
+            let key = parent
+                .pop()
+                .ok_or_else(|| StoreError::InvalidPointer(path.clone()))?;
+            builder.push("data_jsonb #> ").push_bind(parent);
+            builder.push(" ? ").push_bind(key);
+        }
+        Filter::JsonPath(path) => {
+            builder
+                .push("data_jsonb @? ")
+                .push_bind(path.clone())
+                .push("::jsonpath");
+        }
+        Filter::Range { path, lower, upper } => {
+            let tokens = path_tokens(path)?;
+            let mut conditions = Vec::new();
+            match lower {
+                Bound::Included(n) => conditions.push((" >= ", n)),
+                Bound::Excluded(n) => conditions.push((" > ", n)),
+                Bound::Unbounded => {}
+            }
+            match upper {
+                Bound::Included(n) => conditions.push((" <= ", n)),
+                Bound::Excluded(n) => conditions.push((" < ", n)),
+                Bound::Unbounded => {}
+            }
+            if conditions.is_empty() {
+                push_number_at(builder, &tokens);
+                builder.push(" IS NOT NULL");
+            }
+            for (index, (operator, n)) in conditions.into_iter().enumerate() {
+                if index > 0 {
+                    builder.push(" AND ");
+                }
+                push_number_at(builder, &tokens);
+                builder
+                    .push(operator)
+                    .push_bind(n.to_string())
+                    .push("::numeric");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pushes the numeric value at `tokens`, or NULL if it is missing or not a
+/// number, so the cast never fails on other types.
+fn push_number_at(builder: &mut QueryBuilder<'static, Postgres>, tokens: &[String]) {
+    builder
+        .push("CASE WHEN jsonb_typeof(data_jsonb #> ")
+        .push_bind(tokens.to_vec());
+    builder
+        .push(") = 'number' THEN (data_jsonb #>> ")
+        .push_bind(tokens.to_vec());
+    builder.push(")::numeric END");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::postgres::PgPoolOptions;
+
+    /// A store whose pool never connects; building queries needs no database.
+    fn store() -> OrderedJsonStore {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        OrderedJsonStore::from_pool(pool)
+    }
+
+    #[tokio::test]
+    async fn filters_are_bound_and_combined_with_and() {
+        let store = store();
+        let query = store
+            .query()
+            .eq("/year", 2010)
+            .contains(serde_json::json!({"genre": "sci-fi"}))
+            .jsonpath("$.cast[*] ? (@.age > 40)")
+            .order_by("/title", Direction::Desc)
+            .limit(5)
+            .offset(10);
+        assert_eq!(
+            query.build().unwrap().sql(),
+            format!(
+                "SELECT {} FROM json_test WHERE data_jsonb #> $1 = $2 AND data_jsonb @> $3 \
+                 AND data_jsonb @? $4::jsonpath ORDER BY data_jsonb #> $5 DESC, id LIMIT $6 OFFSET $7",
+                store.strategy().columns()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn ranges_compare_numbers_only() {
+        let store = store();
+        let number = |a: usize, b: usize| {
+            format!(
+                "CASE WHEN jsonb_typeof(data_jsonb #> ${}) = 'number' THEN (data_jsonb #>> ${})::numeric END",
+                a, b
+            )
+        };
+        let sql = store
+            .query()
+            .range("/year", 2000..=2010)
+            .build()
+            .unwrap()
+            .sql()
+            .to_string();
+        assert!(sql.ends_with(&format!(
+            "WHERE {} >= $3::numeric AND {} <= $6::numeric ORDER BY id",
+            number(1, 2),
+            number(4, 5)
+        )));
+        let sql = store
+            .query()
+            .range::<i64>("/rating", ..)
+            .build()
+            .unwrap()
+            .sql()
+            .to_string();
+        assert!(sql.ends_with(&format!("WHERE {} IS NOT NULL ORDER BY id", number(1, 2))));
+    }
+
+    #[tokio::test]
+    async fn range_bounds_keep_their_exact_value() {
+        let store = store();
+        let number = |text: &str| text.parse::<Number>().unwrap();
+        let big = number("12345678901234567891");
+        let query = store.query().range("/n", big.clone()..=big.clone());
+        assert_eq!(
+            query.filters,
+            [Filter::Range {
+                path: "/n".to_string(),
+                lower: Bound::Included(big.clone()),
+                upper: Bound::Included(big),
+            }]
+        );
+        assert_eq!(
+            store
+                .query()
+                .range("/n", number("0.5")..number("1e2"))
+                .filters,
+            [Filter::Range {
+                path: "/n".to_string(),
+                lower: Bound::Included(number("0.5")),
+                upper: Bound::Excluded(number("1e2")),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn exists_splits_off_the_last_token() {
+        let store = store();
+        let query = store.query().exists("/cast/0/a~1b");
+        assert!(query
+            .build()
+            .unwrap()
+            .sql()
+            .contains("data_jsonb #> $1 ? $2"));
+        for pointer in ["director", ""] {
+            assert!(matches!(
+                store.query().exists(pointer).build(),
+                Err(StoreError::InvalidPointer(p)) if p == pointer
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_pointers_and_text_tables_are_rejected() {
+        let store = store();
+        assert!(matches!(
+            store.query().eq("year", 2010).build(),
+            Err(StoreError::InvalidPointer(pointer)) if pointer == "year"
+        ));
+        assert!(matches!(
+            store.query().order_by("title", Direction::Asc).build(),
+            Err(StoreError::InvalidPointer(_))
+        ));
+        let text = store.with_strategy(crate::StorageStrategy::Text);
+        assert!(matches!(
+            text.query().build(),
+            Err(StoreError::NotQueryable(crate::StorageStrategy::Text))
+        ));
+    }
+}
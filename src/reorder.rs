@@ -0,0 +1,110 @@
+//! Re-sorting object keys to match a reference document or JSON Schema.
+//!
+//! Useful for values read back from `jsonb`, whose keys come out shortest
+//! first, when the original text is not available.
+
+use crate::schema::{OrderingSchema, UnknownKeys};
+use serde_json::{Map, Value};
+
+/// Returns `value` with the keys of every object rearranged to follow the
+/// corresponding object in `template`.
+///
+/// Keys the template does not mention keep their relative order after the
+/// template's keys. Array elements follow the template element at the same
+/// index, or the template's last element if the template array is shorter,
+/// so a single example element is enough to order a homogeneous array.
+pub fn reorder_by_template(value: &Value, template: &Value) -> Value {
+    match (value, template) {
+        (Value::Object(map), Value::Object(template_map)) => {
+            let mut object = Map::with_capacity(map.len());
+            for (key, child_template) in template_map {
+                if let Some(child) = map.get(key) {
+                    object.insert(key.clone(), reorder_by_template(child, child_template));
+                }
+            }
+            for (key, child) in map {
+                if !template_map.contains_key(key) {
+                    object.insert(key.clone(), child.clone());
+                }
+            }
+            Value::Object(object)
+        }
+        (Value::Array(items), Value::Array(template_items)) if !template_items.is_empty() => {
+            Value::Array(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, child)| {
+                        let child_template = template_items.get(index).or(template_items.last());
+                        reorder_by_template(child, child_template.unwrap_or(&Value::Null))
+                    })
+                    .collect(),
+            )
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Like [`reorder_by_template`], but takes the order from a JSON Schema:
+/// `properties` in declaration order, `items` / `prefixItems` for arrays and
+/// `additionalProperties` for keys the schema does not list. See
+/// [`OrderingSchema::from_json_schema`].
+pub fn reorder_by_schema(value: &Value, schema: &Value) -> Value {
+    OrderingSchema::from_json_schema(schema)
+        .apply(value, UnknownKeys::Append)
+        .expect("appending unknown keys never fails")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn templates_order_nested_objects() {
+        let template = json!({"title": "", "year": 0, "cast": [{"name": "", "role": ""}]});
+        let value = json!({
+            "cast": [{"role": "Cobb", "name": "Leo"}, {"role": "Arthur", "name": "Joseph", "age": 30}],
+            "rating": 8.8,
+            "year": 2010,
+            "title": "Inception"
+        });
+        assert_eq!(
+            reorder_by_template(&value, &template).to_string(),
+            r#"{"title":"Inception","year":2010,"cast":[{"name":"Leo","role":"Cobb"},{"name":"Joseph","role":"Arthur","age":30}],"rating":8.8}"#
+        );
+    }
+
+    #[test]
+    fn mismatched_templates_leave_values_alone() {
+        let value = json!({"b": [{"y": 1, "x": 2}], "a": 1});
+        assert_eq!(reorder_by_template(&value, &json!([1])), value);
+        assert_eq!(
+            reorder_by_template(&value, &json!({"b": []})).to_string(),
+            r#"{"b":[{"y":1,"x":2}],"a":1}"#
+        );
+    }
+
+    #[test]
+    fn schemas_order_properties_and_items() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer"},
+                "point": {"prefixItems": [{"properties": {"x": {}, "y": {}}}]},
+                "tags": {"items": {"properties": {"name": {}, "weight": {}}}}
+            },
+            "additionalProperties": {"properties": {"z": {}, "a": {}}}
+        });
+        let value = json!({
+            "extra": {"a": 1, "z": 2},
+            "tags": [{"weight": 1, "name": "x"}],
+            "point": [{"y": 2, "x": 1}],
+            "id": 1
+        });
+        assert_eq!(
+            reorder_by_schema(&value, &schema).to_string(),
+            r#"{"id":1,"point":[{"x":1,"y":2}],"tags":[{"name":"x","weight":1}],"extra":{"z":2,"a":1}}"#
+        );
+    }
+}
@@ -0,0 +1,531 @@
+//! Schema-driven key ordering for documents whose original text is not
+//! available (or whose order should follow a type definition instead).
+
+use crate::document::OrderedDocument;
+use crate::error::{Result, StoreError};
+use crate::ordered::join_pointer;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+/// The desired key order of a document, level by level.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OrderingSchema {
+    /// No ordering constraints; the value is left as is.
+    #[default]
+    Any,
+    Object {
+        /// Known keys, in the order they should be emitted.
+        properties: Vec<(String, OrderingSchema)>,
+        /// Schema for keys not listed in `properties`. `Some` marks the object
+        /// as an open map whose extra keys are never rejected.
+        additional: Option<Box<OrderingSchema>>,
+    },
+    Array {
+        /// Schemas of the leading elements of a tuple-like array.
+        prefix: Vec<OrderingSchema>,
+        /// Schema of every element after `prefix`.
+        items: Box<OrderingSchema>,
+    },
+}
+
+/// What to do with object keys an [`OrderingSchema`] does not list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownKeys {
+    /// Emit them after the known keys, in their existing order.
+    #[default]
+    Append,
+    /// Emit them after the known keys, sorted bytewise.
+    Sort,
+    /// Fail with [`StoreError::UnknownKey`].
+    Reject,
+}
+
+impl FromStr for UnknownKeys {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "append" => Ok(UnknownKeys::Append),
+            "sort" => Ok(UnknownKeys::Sort),
+            "reject" => Ok(UnknownKeys::Reject),
+            other => Err(format!(
+                "unknown key policy {:?} (expected append, sort or reject)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UnknownKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnknownKeys::Append => "append",
+            UnknownKeys::Sort => "sort",
+            UnknownKeys::Reject => "reject",
+        })
+    }
+}
+
+impl OrderingSchema {
+    pub fn object<K: Into<String>>(
+        properties: impl IntoIterator<Item = (K, OrderingSchema)>,
+    ) -> Self {
+        OrderingSchema::Object {
+            properties: properties
+                .into_iter()
+                .map(|(key, schema)| (key.into(), schema))
+                .collect(),
+            additional: None,
+        }
+    }
+
+    pub fn array(items: OrderingSchema) -> Self {
+        OrderingSchema::Array {
+            prefix: Vec::new(),
+            items: Box::new(items),
+        }
+    }
+
+    /// Derives the ordering from a JSON Schema: `properties` in declaration
+    /// order, `additionalProperties`, `items` and `prefixItems`, following
+    /// local `$ref`s into `$defs` / `definitions`.
+    pub fn from_json_schema(schema: &Value) -> Self {
+        Self::from_json_schema_node(schema, schema, &mut Vec::new())
+    }
+
+    fn from_json_schema_node<'a>(
+        node: &'a Value,
+        root: &'a Value,
+        refs: &mut Vec<&'a str>,
+    ) -> Self {
+        if let Some(reference) = node.get("$ref").and_then(Value::as_str) {
+            // A recursive reference cannot be expanded; leave that level alone.
+            if refs.contains(&reference) {
+                return OrderingSchema::Any;
+            }
+            let Some(target) = reference
+                .strip_prefix('#')
+                .and_then(|pointer| root.pointer(pointer))
+            else {
+                return OrderingSchema::Any;
+            };
+            refs.push(reference);
+            let schema = Self::from_json_schema_node(target, root, refs);
+            refs.pop();
+            return schema;
+        }
+
+        if let Some(properties) = node.get("properties").and_then(Value::as_object) {
+            let additional = match node.get("additionalProperties") {
+                Some(Value::Bool(true)) => Some(Box::new(OrderingSchema::Any)),
+                Some(additional @ Value::Object(_)) => Some(Box::new(Self::from_json_schema_node(
+                    additional, root, refs,
+                ))),
+                _ => None,
+            };
+            return OrderingSchema::Object {
+                properties: properties
+                    .iter()
+                    .map(|(key, property)| {
+                        (
+                            key.clone(),
+                            Self::from_json_schema_node(property, root, refs),
+                        )
+                    })
+                    .collect(),
+                additional,
+            };
+        }
+
+        if let Some(additional @ Value::Object(_)) = node.get("additionalProperties") {
+            return OrderingSchema::Object {
+                properties: Vec::new(),
+                additional: Some(Box::new(Self::from_json_schema_node(
+                    additional, root, refs,
+                ))),
+            };
+        }
+
+        let prefix = node.get("prefixItems").and_then(Value::as_array);
+        match (prefix, node.get("items")) {
+            (None, None) => OrderingSchema::Any,
+            // Draft 4-2019 tuple form: `"items": [..]`.
+            (None, Some(Value::Array(tuple))) => OrderingSchema::Array {
+                prefix: tuple
+                    .iter()
+                    .map(|item| Self::from_json_schema_node(item, root, refs))
+                    .collect(),
+                items: Box::new(OrderingSchema::Any),
+            },
+            (prefix, items) => OrderingSchema::Array {
+                prefix: prefix
+                    .into_iter()
+                    .flatten()
+                    .map(|item| Self::from_json_schema_node(item, root, refs))
+                    .collect(),
+                items: Box::new(items.map_or(OrderingSchema::Any, |items| {
+                    Self::from_json_schema_node(items, root, refs)
+                })),
+            },
+        }
+    }
+
+    /// Returns `value` with every object's keys in schema order.
+    pub fn apply(&self, value: &Value, unknown_keys: UnknownKeys) -> Result<Value> {
+        self.apply_at(value, unknown_keys, "")
+    }
+
+    fn apply_at(&self, value: &Value, unknown_keys: UnknownKeys, pointer: &str) -> Result<Value> {
+        match (self, value) {
+            (
+                OrderingSchema::Object {
+                    properties,
+                    additional,
+                },
+                Value::Object(map),
+            ) => {
+                let mut object = Map::with_capacity(map.len());
+                for (key, schema) in properties {
+                    if let Some(child) = map.get(key) {
+                        let child =
+                            schema.apply_at(child, unknown_keys, &join_pointer(pointer, key))?;
+                        object.insert(key.clone(), child);
+                    }
+                }
+
+                let known: HashSet<&str> = properties.iter().map(|(key, _)| key.as_str()).collect();
+                let mut unknown: Vec<&String> = map
+                    .keys()
+                    .filter(|key| !known.contains(key.as_str()))
+                    .collect();
+                if let (Some(key), None, UnknownKeys::Reject) =
+                    (unknown.first(), additional, unknown_keys)
+                {
+                    return Err(StoreError::UnknownKey {
+                        pointer: pointer.to_string(),
+                        key: key.to_string(),
+                    });
+                }
+                if unknown_keys == UnknownKeys::Sort {
+                    unknown.sort();
+                }
+                let schema = additional.as_deref().unwrap_or(&OrderingSchema::Any);
+                for key in unknown {
+                    let child =
+                        schema.apply_at(&map[key], unknown_keys, &join_pointer(pointer, key))?;
+                    object.insert(key.clone(), child);
+                }
+                Ok(Value::Object(object))
+            }
+            (OrderingSchema::Array { prefix, items }, Value::Array(elements)) => elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| {
+                    let schema = prefix.get(index).unwrap_or(items);
+                    schema.apply_at(
+                        element,
+                        unknown_keys,
+                        &join_pointer(pointer, &index.to_string()),
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array),
+            _ => Ok(value.clone()),
+        }
+    }
+}
+
+/// Types that know the key order of their serialized form. Implement with
+/// `#[derive(KeyOrder)]`, which honours serde's `rename`, `rename_all`,
+/// `skip` and `flatten` attributes.
+pub trait KeyOrder {
+    fn ordering_schema() -> OrderingSchema;
+}
+
+macro_rules! unordered {
+    ($($ty:ty),*) => {
+        $(
+            impl KeyOrder for $ty {
+                fn ordering_schema() -> OrderingSchema {
+                    OrderingSchema::Any
+                }
+            }
+        )*
+    };
+}
+
+unordered!(
+    bool,
+    char,
+    String,
+    str,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    f32,
+    f64,
+    (),
+    Value
+);
+
+impl<T: KeyOrder + ?Sized> KeyOrder for &T {
+    fn ordering_schema() -> OrderingSchema {
+        T::ordering_schema()
+    }
+}
+
+impl<T: KeyOrder + ?Sized> KeyOrder for Box<T> {
+    fn ordering_schema() -> OrderingSchema {
+        T::ordering_schema()
+    }
+}
+
+impl<T: KeyOrder> KeyOrder for Option<T> {
+    fn ordering_schema() -> OrderingSchema {
+        T::ordering_schema()
+    }
+}
+
+impl<T: KeyOrder> KeyOrder for Vec<T> {
+    fn ordering_schema() -> OrderingSchema {
+        OrderingSchema::array(T::ordering_schema())
+    }
+}
+
+impl<T: KeyOrder> KeyOrder for VecDeque<T> {
+    fn ordering_schema() -> OrderingSchema {
+        OrderingSchema::array(T::ordering_schema())
+    }
+}
+
+impl<T: KeyOrder> KeyOrder for [T] {
+    fn ordering_schema() -> OrderingSchema {
+        OrderingSchema::array(T::ordering_schema())
+    }
+}
+
+impl<T: KeyOrder, const N: usize> KeyOrder for [T; N] {
+    fn ordering_schema() -> OrderingSchema {
+        OrderingSchema::array(T::ordering_schema())
+    }
+}
+
+impl<K, V: KeyOrder, S> KeyOrder for HashMap<K, V, S> {
+    fn ordering_schema() -> OrderingSchema {
+        OrderingSchema::Object {
+            properties: Vec::new(),
+            additional: Some(Box::new(V::ordering_schema())),
+        }
+    }
+}
+
+impl<K, V: KeyOrder> KeyOrder for BTreeMap<K, V> {
+    fn ordering_schema() -> OrderingSchema {
+        OrderingSchema::Object {
+            properties: Vec::new(),
+            additional: Some(Box::new(V::ordering_schema())),
+        }
+    }
+}
+
+/// Ordering schemas by document type, consulted on the store's read path.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, (OrderingSchema, UnknownKeys)>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        doc_type: &str,
+        schema: OrderingSchema,
+        unknown_keys: UnknownKeys,
+    ) -> &mut Self {
+        self.schemas
+            .insert(doc_type.to_string(), (schema, unknown_keys));
+        self
+    }
+
+    pub fn register_json_schema(
+        &mut self,
+        doc_type: &str,
+        json_schema: &Value,
+        unknown_keys: UnknownKeys,
+    ) -> &mut Self {
+        self.register(
+            doc_type,
+            OrderingSchema::from_json_schema(json_schema),
+            unknown_keys,
+        )
+    }
+
+    pub fn register_type<T: KeyOrder + ?Sized>(
+        &mut self,
+        doc_type: &str,
+        unknown_keys: UnknownKeys,
+    ) -> &mut Self {
+        self.register(doc_type, T::ordering_schema(), unknown_keys)
+    }
+
+    /// Registers `T`'s derived schema under its [`OrderedDocument::DOC_TYPE`].
+    pub fn register_document<T: OrderedDocument>(
+        &mut self,
+        unknown_keys: UnknownKeys,
+    ) -> &mut Self {
+        self.register_type::<T>(T::DOC_TYPE, unknown_keys)
+    }
+
+    pub fn get(&self, doc_type: &str) -> Option<(&OrderingSchema, UnknownKeys)> {
+        self.schemas
+            .get(doc_type)
+            .map(|(schema, unknown_keys)| (schema, *unknown_keys))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn objects_follow_property_order() {
+        let schema = OrderingSchema::object([
+            ("title", OrderingSchema::Any),
+            (
+                "cast",
+                OrderingSchema::array(OrderingSchema::object([
+                    ("name", OrderingSchema::Any),
+                    ("role", OrderingSchema::Any),
+                ])),
+            ),
+        ]);
+        let value = json!({"cast": [{"role": "Cobb", "name": "Leo"}], "title": "Inception"});
+        assert_eq!(
+            schema
+                .apply(&value, UnknownKeys::Append)
+                .unwrap()
+                .to_string(),
+            r#"{"title":"Inception","cast":[{"name":"Leo","role":"Cobb"}]}"#
+        );
+    }
+
+    #[test]
+    fn unknown_keys_append_sort_or_reject() {
+        let schema = OrderingSchema::object([("id", OrderingSchema::Any)]);
+        let value = json!({"zeta": 1, "id": 0, "alpha": {"b": 1, "a": 2}});
+        assert_eq!(
+            schema
+                .apply(&value, UnknownKeys::Append)
+                .unwrap()
+                .to_string(),
+            r#"{"id":0,"zeta":1,"alpha":{"b":1,"a":2}}"#
+        );
+        assert_eq!(
+            schema.apply(&value, UnknownKeys::Sort).unwrap().to_string(),
+            r#"{"id":0,"alpha":{"b":1,"a":2},"zeta":1}"#
+        );
+        match schema.apply(&value, UnknownKeys::Reject) {
+            Err(StoreError::UnknownKey { pointer, key }) => {
+                assert_eq!((pointer.as_str(), key.as_str()), ("", "zeta"))
+            }
+            other => panic!("expected an unknown key error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn open_maps_accept_any_key() {
+        let schema =
+            OrderingSchema::object([("scores", HashMap::<String, Vec<u8>>::ordering_schema())]);
+        let value = json!({"scores": {"b": [1], "a": [2]}});
+        assert_eq!(schema.apply(&value, UnknownKeys::Reject).unwrap(), value);
+        match schema.apply(&json!({"scores": {}, "x": 1}), UnknownKeys::Reject) {
+            Err(StoreError::UnknownKey { pointer, key }) => {
+                assert_eq!((pointer.as_str(), key.as_str()), ("", "x"))
+            }
+            other => panic!("expected an unknown key error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_schemas_follow_refs_and_tuples() {
+        let json_schema = json!({
+            "properties": {
+                "b": {"$ref": "#/$defs/node"},
+                "a": {"items": [{"properties": {"y": {}, "x": {}}}]}
+            },
+            "$defs": {
+                "node": {
+                    "properties": {"value": {}, "next": {"$ref": "#/$defs/node"}},
+                    "additionalProperties": true
+                }
+            }
+        });
+        let schema = OrderingSchema::from_json_schema(&json_schema);
+        let node = OrderingSchema::Object {
+            properties: vec![
+                ("value".to_string(), OrderingSchema::Any),
+                ("next".to_string(), OrderingSchema::Any),
+            ],
+            additional: Some(Box::new(OrderingSchema::Any)),
+        };
+        let tuple = OrderingSchema::Array {
+            prefix: vec![OrderingSchema::object([
+                ("y", OrderingSchema::Any),
+                ("x", OrderingSchema::Any),
+            ])],
+            items: Box::new(OrderingSchema::Any),
+        };
+        assert_eq!(schema, OrderingSchema::object([("b", node), ("a", tuple)]));
+    }
+
+    #[test]
+    fn unknown_key_policies_parse() {
+        for policy in [UnknownKeys::Append, UnknownKeys::Sort, UnknownKeys::Reject] {
+            assert_eq!(policy.to_string().parse::<UnknownKeys>(), Ok(policy));
+        }
+        assert!("keep".parse::<UnknownKeys>().is_err());
+    }
+
+    #[test]
+    fn registry_looks_up_by_document_type() {
+        let mut registry = SchemaRegistry::new();
+        assert!(registry.is_empty());
+        registry
+            .register_json_schema(
+                "movie",
+                &json!({"properties": {"title": {}}}),
+                UnknownKeys::Sort,
+            )
+            .register_type::<Vec<String>>("tags", UnknownKeys::Append);
+        let (schema, policy) = registry.get("movie").unwrap();
+        assert_eq!(
+            schema,
+            &OrderingSchema::object([("title", OrderingSchema::Any)])
+        );
+        assert_eq!(policy, UnknownKeys::Sort);
+        assert_eq!(
+            registry.get("tags").unwrap().0,
+            &OrderingSchema::array(OrderingSchema::Any)
+        );
+        assert!(registry.get("book").is_none());
+    }
+}
@@ -0,0 +1,455 @@
+//! A JSON parser that records the byte span of every value, so fragments of
+//! a stored document can be cut out of its original text.
+
+use crate::error::{Result, StoreError};
+use crate::ordered::{parse_index, set_member, split_pointer, OrderedJson};
+use linked_hash_map::LinkedHashMap;
+use serde_json::Number;
+use std::ops::Range;
+
+/// Nesting limit, matching serde_json's, so hostile input cannot overflow
+/// the stack.
+const MAX_DEPTH: usize = 128;
+
+/// A parsed JSON value together with the byte range it occupies in the
+/// source text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedJson {
+    pub span: Range<usize>,
+    pub kind: SpannedKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpannedKind {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<SpannedJson>),
+    /// Members in source order. Duplicate keys are all kept.
+    Object(Vec<SpannedMember>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedMember {
+    pub key: String,
+    /// Span of the key including its quotes.
+    pub key_span: Range<usize>,
+    pub value: SpannedJson,
+}
+
+impl SpannedJson {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            depth: 0,
+        };
+        parser.skip_whitespace();
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// The source text of this value.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.clone()]
+    }
+
+    /// Resolves an RFC 6901 JSON Pointer. Where an object has a duplicate
+    /// key, the last occurrence wins, as it does for `serde_json` and `jsonb`.
+    pub fn pointer(&self, pointer: &str) -> Option<&SpannedJson> {
+        split_pointer(pointer)?
+            .iter()
+            .try_fold(self, |target, token| match &target.kind {
+                SpannedKind::Object(members) => members
+                    .iter()
+                    .rev()
+                    .find(|m| &m.key == token)
+                    .map(|m| &m.value),
+                SpannedKind::Array(items) => parse_index(token).and_then(|i| items.get(i)),
+                _ => None,
+            })
+    }
+
+    /// Converts to an [`OrderedJson`] in source order.
+    pub fn to_ordered(&self) -> OrderedJson {
+        match &self.kind {
+            SpannedKind::Null => OrderedJson::Null,
+            SpannedKind::Bool(b) => OrderedJson::Bool(*b),
+            SpannedKind::Number(n) => OrderedJson::Number(n.clone()),
+            SpannedKind::String(s) => OrderedJson::String(s.clone()),
+            SpannedKind::Array(items) => {
+                OrderedJson::Array(items.iter().map(SpannedJson::to_ordered).collect())
+            }
+            SpannedKind::Object(members) => {
+                let mut map = LinkedHashMap::new();
+                for member in members {
+                    set_member(&mut map, member.key.clone(), member.value.to_ordered());
+                }
+                OrderedJson::Object(map)
+            }
+        }
+    }
+}
+
+/// The 1-based line and column (in characters) of byte `offset` in `text`.
+pub fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> StoreError {
+        let (line, column) = line_column(self.text, self.pos);
+        StoreError::Syntax {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &str) -> Result<()> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(message));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<SpannedJson> {
+        let start = self.pos;
+        let kind = match self.peek() {
+            Some(b'{') => self.nested(Self::object)?,
+            Some(b'[') => self.nested(Self::array)?,
+            Some(b'"') => SpannedKind::String(self.string()?),
+            Some(b'-' | b'0'..=b'9') => SpannedKind::Number(self.number()?),
+            Some(b't') => self.literal("true", SpannedKind::Bool(true))?,
+            Some(b'f') => self.literal("false", SpannedKind::Bool(false))?,
+            Some(b'n') => self.literal("null", SpannedKind::Null)?,
+            Some(_) => return Err(self.error("expected value")),
+            None => return Err(self.error("EOF while parsing a value")),
+        };
+        Ok(SpannedJson {
+            span: start..self.pos,
+            kind,
+        })
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<SpannedKind>) -> Result<SpannedKind> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.depth += 1;
+        let kind = parse(self);
+        self.depth -= 1;
+        kind
+    }
+
+    fn literal(&mut self, literal: &str, kind: SpannedKind) -> Result<SpannedKind> {
+        if !self.text[self.pos..].starts_with(literal) {
+            return Err(self.error("expected value"));
+        }
+        self.pos += literal.len();
+        Ok(kind)
+    }
+
+    fn object(&mut self) -> Result<SpannedKind> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(SpannedKind::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("key must be a string"));
+            }
+            let key_start = self.pos;
+            let key = self.string()?;
+            let key_span = key_start..self.pos;
+            self.skip_whitespace();
+            self.expect(b':', "expected `:`")?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            members.push(SpannedMember {
+                key,
+                key_span,
+                value,
+            });
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(SpannedKind::Object(members));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<SpannedKind> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(SpannedKind::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(SpannedKind::Array(items));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let Some(special) = rest.find(|c: char| c == '"' || c == '\\' || c < ' ') else {
+                self.pos = self.text.len();
+                return Err(self.error("EOF while parsing a string"));
+            };
+            out.push_str(&rest[..special]);
+            self.pos += special;
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            out.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    out.push(escaped);
+                }
+                _ => return Err(self.error("control character in string")),
+            }
+        }
+    }
+
+    /// Decodes the digits after `\u`, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high)
+                .ok_or_else(|| self.error("lone trailing surrogate in hex escape"));
+        }
+        if !self.text[self.pos..].starts_with("\\u") {
+            return Err(self.error("lone leading surrogate in hex escape"));
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("invalid surrogate pair in hex escape"));
+        }
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        Ok(char::from_u32(code).expect("surrogate pairs decode to valid chars"))
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("EOF while parsing a string"))?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(self.error("invalid escape"));
+        }
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("checked hex digits"))
+    }
+
+    fn number(&mut self) -> Result<Number> {
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            let from = parser.pos;
+            while let Some(b'0'..=b'9') = parser.peek() {
+                parser.pos += 1;
+            }
+            parser.pos > from
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        serde_json::from_str(&self.text[start..self.pos])
+            .map_err(|_| self.error("number out of range"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax_error(text: &str) -> (usize, usize, String) {
+        match SpannedJson::parse(text) {
+            Err(StoreError::Syntax {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spans_cover_each_value_and_key() {
+        let text = r#" {"a": [1, "x\n"], "b" : {"c": null}} "#;
+        let root = SpannedJson::parse(text).unwrap();
+        assert_eq!(root.text(text), r#"{"a": [1, "x\n"], "b" : {"c": null}}"#);
+        assert_eq!(root.pointer("/a/1").unwrap().text(text), r#""x\n""#);
+        assert_eq!(root.pointer("/b").unwrap().text(text), r#"{"c": null}"#);
+        let SpannedKind::Object(members) = &root.kind else {
+            panic!("root is an object")
+        };
+        assert_eq!(&text[members[1].key_span.clone()], r#""b""#);
+        assert_eq!(
+            root.pointer("/a/1").unwrap().kind,
+            SpannedKind::String("x\n".to_string())
+        );
+    }
+
+    #[test]
+    fn numbers_keep_their_text() {
+        let text = "[1.50, 1e2, -0, 12345678901234567891]";
+        let root = SpannedJson::parse(text).unwrap();
+        let SpannedKind::Array(items) = &root.kind else {
+            panic!("root is an array")
+        };
+        let numbers: Vec<&str> = items.iter().map(|item| item.text(text)).collect();
+        assert_eq!(numbers, ["1.50", "1e2", "-0", "12345678901234567891"]);
+    }
+
+    #[test]
+    fn duplicate_keys_are_all_kept_and_the_last_wins() {
+        let text = r#"{"b": 1, "a": 2, "b": 3}"#;
+        let root = SpannedJson::parse(text).unwrap();
+        let SpannedKind::Object(members) = &root.kind else {
+            panic!("root is an object")
+        };
+        assert_eq!(members.len(), 3);
+        assert_eq!(root.pointer("/b").unwrap().text(text), "3");
+        // In the first key's position, like serde_json's `preserve_order`.
+        assert_eq!(root.to_ordered().to_string(), r#"{"b":3,"a":2}"#);
+    }
+
+    #[test]
+    fn pointers_unescape_and_reject_bad_indexes() {
+        let text = r#"{"a/b": {"~c": [0, 1]}}"#;
+        let root = SpannedJson::parse(text).unwrap();
+        assert_eq!(root.pointer("/a~1b/~0c/1").unwrap().text(text), "1");
+        assert!(root.pointer("/a~1b/~0c/01").is_none());
+        assert!(root.pointer("/a~1b/~0c/2").is_none());
+        assert!(root.pointer("a").is_none());
+        assert_eq!(root.pointer("").unwrap().span, 0..text.len());
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        assert_eq!(
+            syntax_error("{\n  \"a\": }"),
+            (2, 8, "expected value".to_string())
+        );
+        assert_eq!(
+            syntax_error("[1] x"),
+            (1, 5, "trailing characters".to_string())
+        );
+        assert_eq!(
+            syntax_error(r#"{"é": tru}"#).2,
+            "expected value".to_string()
+        );
+        assert_eq!(
+            syntax_error(&"[".repeat(MAX_DEPTH + 1)).2,
+            "recursion limit exceeded"
+        );
+    }
+
+    #[test]
+    fn escapes_decode_including_surrogate_pairs() {
+        let root = SpannedJson::parse(r#""\u00e9\ud83d\ude00\/\t""#).unwrap();
+        assert_eq!(root.kind, SpannedKind::String("é😀/\t".to_string()));
+        assert_eq!(
+            syntax_error(r#""\ud83d""#).2,
+            "lone leading surrogate in hex escape"
+        );
+    }
+
+    #[test]
+    fn line_column_counts_characters() {
+        let text = "{\n  \"é\": 1}";
+        assert_eq!(line_column(text, 0), (1, 1));
+        assert_eq!(line_column(text, text.find('1').unwrap()), (2, 8));
+    }
+}
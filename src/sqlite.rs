@@ -0,0 +1,376 @@
+//! A [`JsonStore`] on SQLite, for tests and small deployments without a
+//! PostgreSQL server.
+//!
+//! SQLite has no `jsonb`: each document is kept only as its original text,
+//! in a `TEXT` column checked with `json_valid`, and queried with the JSON1
+//! functions. Documents come back as they were stored, like the `text`
+//! strategy on PostgreSQL.
+
+use crate::backend::JsonStore;
+use crate::error::{Result, StoreError};
+use crate::jcs;
+use crate::ordered::{parse_index, split_pointer};
+use crate::store::StoredJson;
+use crate::strategy::StorageStrategy;
+use crate::verify::semantically_equal;
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::Row;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Opens `database_url` (e.g. `sqlite://documents.db` or
+    /// `sqlite::memory:`), creating the file if needed.
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        // Every connection to `:memory:` gets its own database, and SQLite
+        // serializes writes anyway.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    fn stored_from_row(row: &SqliteRow) -> Result<StoredJson> {
+        Ok(StoredJson {
+            id: row.try_get("id")?,
+            strategy: StorageStrategy::Text,
+            data_jsonb: None,
+            raw_text: row.try_get("raw_text")?,
+            key_order: None,
+            content_hash: row.try_get("content_hash")?,
+            doc_type: row.try_get("doc_type")?,
+            schema_ordered: None,
+        })
+    }
+}
+
+#[async_trait]
+impl JsonStore for SqliteStore {
+    async fn ensure_table_exists(&self) -> Result<()> {
+        sqlx::raw_sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS json_test (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                raw_text TEXT NOT NULL CHECK (json_valid(raw_text)),
+                content_hash TEXT,
+                doc_type TEXT
+            );
+            CREATE INDEX IF NOT EXISTS json_test_content_hash_idx ON json_test (content_hash);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_json(&self, json_data: &str) -> Result<i32> {
+        let content_hash = jcs::content_hash_exact_str(json_data)?;
+        // A row whose id does not fit in an i32 could never be read back, so
+        // it is rolled back rather than kept.
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query("INSERT INTO json_test (raw_text, content_hash) VALUES (?1, ?2)")
+            .bind(json_data)
+            .bind(content_hash)
+            .execute(&mut *tx)
+            .await?;
+        let rowid = result.last_insert_rowid();
+        let id = i32::try_from(rowid).map_err(|_| StoreError::IdOutOfRange(rowid))?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn get_json_by_id(&self, id: i32) -> Result<StoredJson> {
+        let row =
+            sqlx::query("SELECT id, raw_text, content_hash, doc_type FROM json_test WHERE id = ?1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?
+                .ok_or(StoreError::NotFound(id))?;
+        let stored = Self::stored_from_row(&row)?;
+        stored.check_content_hash()?;
+        Ok(stored)
+    }
+
+    async fn list(&self) -> Result<Vec<StoredJson>> {
+        let rows =
+            sqlx::query("SELECT id, raw_text, content_hash, doc_type FROM json_test ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?;
+        rows.iter().map(Self::stored_from_row).collect()
+    }
+
+    /// Narrows the rows with `json_type` (and `json_extract` for strings) in
+    /// SQL, then compares the values exactly in Rust.
+    async fn find_by_pointer(&self, pointer: &str, value: &Value) -> Result<Vec<StoredJson>> {
+        let tokens = split_pointer(pointer)
+            .ok_or_else(|| StoreError::InvalidPointer(pointer.to_string()))?;
+        let rows = match sqlite_path(&tokens) {
+            Some(path) => {
+                sqlx::query(
+                    r#"
+                    SELECT id, raw_text, content_hash, doc_type
+                    FROM json_test
+                    WHERE json_type(raw_text, ?1) IN (?2, ?3)
+                      AND (?4 IS NULL OR json_extract(raw_text, ?1) = ?4)
+                    ORDER BY id
+                    "#,
+                )
+                .bind(&path)
+                .bind(json_types(value).0)
+                .bind(json_types(value).1)
+                .bind(value.as_str())
+                .fetch_all(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query(
+                    "SELECT id, raw_text, content_hash, doc_type FROM json_test ORDER BY id",
+                )
+                .fetch_all(&self.pool)
+                .await?
+            }
+        };
+
+        let mut found = Vec::new();
+        for row in &rows {
+            let stored = Self::stored_from_row(row)?;
+            let document: Value = stored
+                .raw_text
+                .as_deref()
+                .map(serde_json::from_str)
+                .transpose()?
+                .unwrap_or_default();
+            if document
+                .pointer(pointer)
+                .is_some_and(|at| semantically_equal(at, value))
+            {
+                found.push(stored);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// The SQLite JSON path for pointer `tokens`, or `None` if it cannot be
+/// written as one, in which case the caller scans every row. That is the
+/// case for keys containing a double quote, and for digit-only tokens, which
+/// a pointer leaves ambiguous between an array index and an object key such
+/// as `"2024"`.
+fn sqlite_path(tokens: &[String]) -> Option<String> {
+    let mut path = String::from("$");
+    for token in tokens {
+        if token.contains('"') || parse_index(token).is_some() {
+            return None;
+        }
+        path.push_str(&format!(".\"{}\"", token));
+    }
+    Some(path)
+}
+
+/// The `json_type` names `value` may have; numbers are `integer` or `real`.
+fn json_types(value: &Value) -> (&'static str, &'static str) {
+    match value {
+        Value::Null => ("null", "null"),
+        Value::Bool(true) => ("true", "true"),
+        Value::Bool(false) => ("false", "false"),
+        Value::Number(_) => ("integer", "real"),
+        Value::String(_) => ("text", "text"),
+        Value::Array(_) => ("array", "array"),
+        Value::Object(_) => ("object", "object"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordered::OrderedJson;
+    use serde_json::json;
+
+    const MOVIE: &str = r#"{"title": "Inception", "year": 2010, "rating": 8.80, "id": 1e2}"#;
+
+    async fn store() -> SqliteStore {
+        let store = SqliteStore::connect("sqlite::memory:").await.unwrap();
+        store.ensure_table_exists().await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn documents_come_back_as_they_were_stored() {
+        let store = store().await;
+        store.ensure_table_exists().await.unwrap();
+        let id = store.insert_json(MOVIE).await.unwrap();
+        let stored = store.get_json_by_id(id).await.unwrap();
+        assert_eq!(stored.raw_text.as_deref(), Some(MOVIE));
+        assert_eq!(stored.data_jsonb, None);
+        assert_eq!(
+            stored.content_hash.as_deref(),
+            Some(jcs::content_hash_exact_str(MOVIE).unwrap().as_str())
+        );
+        assert_eq!(
+            stored.document().unwrap(),
+            MOVIE.parse::<OrderedJson>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn ids_count_from_one_and_list_in_order() {
+        let store = store().await;
+        assert_eq!(store.insert_json("[1]").await.unwrap(), 1);
+        assert_eq!(store.insert_json("[2]").await.unwrap(), 2);
+        let ids: Vec<i32> = store
+            .list()
+            .await
+            .unwrap()
+            .iter()
+            .map(|stored| stored.id)
+            .collect();
+        assert_eq!(ids, [1, 2]);
+        assert!(matches!(
+            store.get_json_by_id(3).await,
+            Err(StoreError::NotFound(3))
+        ));
+        assert!(store.insert_json("[1,]").await.is_err());
+        assert_eq!(store.list().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn ids_past_i32_max_are_rejected() {
+        let store = store().await;
+        sqlx::query("INSERT INTO json_test (id, raw_text) VALUES (?1, '[]')")
+            .bind(i32::MAX)
+            .execute(store.pool())
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.insert_json("[1]").await,
+            Err(StoreError::IdOutOfRange(id)) if id == i64::from(i32::MAX) + 1
+        ));
+        assert_eq!(store.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn edited_rows_fail_the_hash_check() {
+        let store = store().await;
+        let id = store.insert_json(r#"{"a": 1}"#).await.unwrap();
+        sqlx::query("UPDATE json_test SET raw_text = '{\"a\": 2}' WHERE id = ?1")
+            .bind(id)
+            .execute(store.pool())
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.get_json_by_id(id).await,
+            Err(StoreError::HashMismatch { .. })
+        ));
+        assert!(
+            sqlx::query("UPDATE json_test SET raw_text = '{' WHERE id = ?1")
+                .bind(id)
+                .execute(store.pool())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn find_by_pointer_compares_values_exactly() {
+        let store = store().await;
+        store.insert_json(MOVIE).await.unwrap();
+        store
+            .insert_json(
+                r#"{"title": "Tenet", "year": 2020, "cast": [{"name": "JD", "born": 1984}]}"#,
+            )
+            .await
+            .unwrap();
+        store
+            .insert_json(r#"{"title": "2010", "a\"b": true}"#)
+            .await
+            .unwrap();
+
+        let ids = |found: Vec<StoredJson>| found.iter().map(|stored| stored.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(store.find_by_pointer("/year", &json!(2010)).await.unwrap()),
+            [1]
+        );
+        assert_eq!(
+            ids(store.find_by_pointer("/id", &json!(100)).await.unwrap()),
+            [1]
+        );
+        assert_eq!(
+            ids(store.find_by_pointer("/rating", &json!(8.8)).await.unwrap()),
+            [1]
+        );
+        assert_eq!(
+            ids(store
+                .find_by_pointer("/title", &json!("2010"))
+                .await
+                .unwrap()),
+            [3]
+        );
+        assert_eq!(
+            ids(store
+                .find_by_pointer("/cast/0", &json!({"born": 1984, "name": "JD"}))
+                .await
+                .unwrap()),
+            [2]
+        );
+        assert_eq!(
+            ids(store.find_by_pointer("/a\"b", &json!(true)).await.unwrap()),
+            [3]
+        );
+        assert!(matches!(
+            store.find_by_pointer("year", &json!(2010)).await,
+            Err(StoreError::InvalidPointer(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn digit_tokens_match_object_keys_too() {
+        let store = store().await;
+        store
+            .insert_json(r#"{"tags": {"2020": [1.0]}}"#)
+            .await
+            .unwrap();
+        store.insert_json(r#"{"tags": [[1]]}"#).await.unwrap();
+        let ids = |found: Vec<StoredJson>| found.iter().map(|stored| stored.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(store
+                .find_by_pointer("/tags/2020/0", &json!(1))
+                .await
+                .unwrap()),
+            [1]
+        );
+        assert_eq!(
+            ids(store.find_by_pointer("/tags/0/0", &json!(1)).await.unwrap()),
+            [2]
+        );
+    }
+
+    #[tokio::test]
+    async fn stores_can_be_shared_across_tasks() {
+        let store: std::sync::Arc<dyn JsonStore> = std::sync::Arc::new(store().await);
+        let task = tokio::spawn({
+            let store = store.clone();
+            async move { store.insert_json(MOVIE).await }
+        });
+        let id = task.await.unwrap().unwrap();
+        assert_eq!(
+            store.get_json_by_id(id).await.unwrap().raw_text.as_deref(),
+            Some(MOVIE)
+        );
+    }
+}
@@ -0,0 +1,638 @@
+use crate::document::OrderedDocument;
+use crate::duplicates::{resolve_duplicates, DuplicateKeys};
+use crate::edit::LosslessJson;
+use crate::enforce::{self, Enforcement};
+use crate::error::{Result, StoreError};
+use crate::jcs;
+use crate::manifest::KeyOrderManifest;
+use crate::migrate;
+use crate::ordered::{split_pointer, OrderedJson};
+use crate::patch::Patch;
+use crate::query::DocumentQuery;
+use crate::schema::{SchemaRegistry, UnknownKeys};
+use crate::span::SpannedJson;
+use crate::strategy::StorageStrategy;
+use crate::verify::{number_differences, NumberDifference};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::postgres::{PgArguments, PgPoolOptions, PgRow};
+use sqlx::query::Query;
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool, Postgres, Row};
+use std::fs;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A stored document as read back through one [`StorageStrategy`]. Columns
+/// the strategy does not keep are `None`.
+#[derive(Debug, Clone)]
+pub struct StoredJson {
+    pub id: i32,
+    pub strategy: StorageStrategy,
+    pub data_jsonb: Option<Value>,
+    pub raw_text: Option<String>,
+    pub key_order: Option<KeyOrderManifest>,
+    /// SHA-256 of [`jcs::canonicalize_exact`] (`dual` strategy only).
+    pub content_hash: Option<String>,
+    pub doc_type: Option<String>,
+    /// The document re-ordered by the ordering schema registered for
+    /// `doc_type`, if any.
+    pub schema_ordered: Option<OrderedJson>,
+}
+
+impl StoredJson {
+    /// The document in the best order available: the schema order if its
+    /// type has a registered schema, otherwise the
+    /// [original document](Self::original_document).
+    pub fn document(&self) -> Result<OrderedJson> {
+        match &self.schema_ordered {
+            Some(schema_ordered) => Ok(schema_ordered.clone()),
+            None => self.original_document(),
+        }
+    }
+
+    /// The document as stored: the raw text if the strategy kept it,
+    /// otherwise the JSONB value re-ordered by the manifest. A
+    /// `jsonb-manifest` row without a manifest is an error, since its
+    /// original order is lost.
+    pub fn original_document(&self) -> Result<OrderedJson> {
+        if let Some(raw_text) = &self.raw_text {
+            return Ok(raw_text.parse()?);
+        }
+        let data = self.data_jsonb.as_ref().unwrap_or(&Value::Null);
+        match (&self.key_order, self.strategy) {
+            (Some(key_order), _) => Ok(key_order.apply(data)),
+            (None, StorageStrategy::JsonbManifest) => Err(StoreError::MissingKeyOrder(self.id)),
+            (None, _) => Ok(KeyOrderManifest::default().apply(data)),
+        }
+    }
+
+    /// Numbers whose text in `raw_text` differs from the `jsonb` value, which
+    /// PostgreSQL normalizes (`1e2` reads back as `100`). Empty unless the
+    /// strategy keeps both.
+    pub fn number_differences(&self) -> Result<Vec<NumberDifference>> {
+        match (&self.raw_text, &self.data_jsonb) {
+            (Some(raw_text), Some(data_jsonb)) => number_differences(raw_text, data_jsonb),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn from_row(row: &PgRow, strategy: StorageStrategy) -> Result<Self> {
+        let key_order: Option<Json<KeyOrderManifest>> = row.try_get("key_order")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            strategy,
+            data_jsonb: row.try_get("data_jsonb")?,
+            raw_text: row.try_get("raw_text")?,
+            key_order: key_order.map(|Json(manifest)| manifest),
+            content_hash: row.try_get("content_hash")?,
+            doc_type: row.try_get("doc_type")?,
+            schema_ordered: None,
+        })
+    }
+
+    /// Recomputes the content hash from `raw_text` and compares it with the
+    /// stored one. Rows without a stored hash pass.
+    pub fn check_content_hash(&self) -> Result<()> {
+        let (Some(stored), Some(raw_text)) = (&self.content_hash, &self.raw_text) else {
+            return Ok(());
+        };
+        let computed = jcs::content_hash_exact_str(raw_text)?;
+        if &computed != stored {
+            return Err(StoreError::HashMismatch {
+                id: self.id,
+                stored: stored.clone(),
+                computed,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A subtree of a stored document, as returned by
+/// [`OrderedJsonStore::get_fragment`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub id: i32,
+    pub pointer: String,
+    /// The subtree in its original key order.
+    pub value: OrderedJson,
+    /// Byte range of the subtree in `raw_text`, for strategies that keep it.
+    pub span: Option<Range<usize>>,
+    /// The subtree's original text, formatting included.
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderedJsonStore {
+    pool: PgPool,
+    strategy: StorageStrategy,
+    schemas: Arc<SchemaRegistry>,
+    enforcement: Option<Enforcement>,
+    duplicate_keys: DuplicateKeys,
+    /// Whether the installed enforcement makes `data_jsonb` a generated
+    /// column, as read from the catalog when the table was set up. Shared by
+    /// every handle on the pool.
+    jsonb_generated: Arc<AtomicBool>,
+}
+
+impl OrderedJsonStore {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await?;
+
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self {
+            pool,
+            strategy: StorageStrategy::default(),
+            schemas: Arc::default(),
+            enforcement: None,
+            duplicate_keys: DuplicateKeys::default(),
+            jsonb_generated: Arc::default(),
+        }
+    }
+
+    /// Returns a handle on the same pool that stores documents with `strategy`.
+    pub fn with_strategy(&self, strategy: StorageStrategy) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            strategy,
+            schemas: self.schemas.clone(),
+            enforcement: self.enforcement,
+            duplicate_keys: self.duplicate_keys,
+            jsonb_generated: self.jsonb_generated.clone(),
+        }
+    }
+
+    /// Returns a handle on the same pool that orders documents read back by
+    /// the schema registered for their `doc_type`.
+    pub fn with_schemas(&self, schemas: SchemaRegistry) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            strategy: self.strategy,
+            schemas: Arc::new(schemas),
+            enforcement: self.enforcement,
+            duplicate_keys: self.duplicate_keys,
+            jsonb_generated: self.jsonb_generated.clone(),
+        }
+    }
+
+    /// Returns a handle on the same pool whose
+    /// [`ensure_table_exists`](Self::ensure_table_exists) installs
+    /// `enforcement` on `json_test`. Without one, whatever is installed is
+    /// left alone.
+    pub fn with_enforcement(&self, enforcement: Enforcement) -> Self {
+        Self {
+            enforcement: Some(enforcement),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle on the same pool that resolves repeated object keys
+    /// in documents it stores according to `policy`.
+    pub fn with_duplicate_keys(&self, policy: DuplicateKeys) -> Self {
+        Self {
+            duplicate_keys: policy,
+            ..self.clone()
+        }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    pub fn strategy(&self) -> StorageStrategy {
+        self.strategy
+    }
+
+    pub fn schemas(&self) -> &SchemaRegistry {
+        &self.schemas
+    }
+
+    pub fn enforcement(&self) -> Option<Enforcement> {
+        self.enforcement
+    }
+
+    pub fn duplicate_keys(&self) -> DuplicateKeys {
+        self.duplicate_keys
+    }
+
+    /// Whether `json_test.data_jsonb` is a generated column that writes must
+    /// leave out. Follows the enforcement installed in the database, not the
+    /// one configured on this handle.
+    pub(crate) fn jsonb_is_generated(&self) -> bool {
+        self.jsonb_generated.load(Ordering::Relaxed)
+    }
+
+    /// Brings the schema up to date by running any pending migrations, then
+    /// installs the configured [`Enforcement`], if any.
+    pub async fn ensure_table_exists(&self) -> Result<()> {
+        migrate::run(&self.pool).await?;
+        self.apply_enforcement().await
+    }
+
+    /// Drops and recreates every table, discarding all stored documents.
+    pub async fn reset(&self) -> Result<()> {
+        migrate::reset(&self.pool).await?;
+        self.apply_enforcement().await
+    }
+
+    /// Installs the configured enforcement, then records what is actually
+    /// installed so writes match the table.
+    async fn apply_enforcement(&self) -> Result<()> {
+        if let Some(enforcement) = self.enforcement {
+            enforce::apply(&self.pool, enforcement).await?;
+        }
+        let installed = enforce::current(&self.pool).await?;
+        self.jsonb_generated
+            .store(installed.derives_jsonb(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Stores `json_data` byte for byte, apart from repeated keys dropped by
+    /// the store's [`DuplicateKeys`] policy.
+    pub async fn insert_json(&self, json_data: &str) -> Result<i32> {
+        let json_data = resolve_duplicates(json_data, self.duplicate_keys)?;
+        let row = self
+            .insert_query(&json_data, None)?
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.try_get("id")?)
+    }
+
+    /// Like [`insert_json`](Self::insert_json), but tags the row with
+    /// `doc_type` so reads apply that type's registered ordering schema.
+    pub async fn insert_typed_json(&self, doc_type: &str, json_data: &str) -> Result<i32> {
+        let json_data = resolve_duplicates(json_data, self.duplicate_keys)?;
+        let row = self
+            .insert_query(&json_data, Some(doc_type))?
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.try_get("id")?)
+    }
+
+    /// Like [`insert_json`](Self::insert_json), but runs on a connection or
+    /// transaction owned by the caller.
+    pub async fn insert_json_with(&self, conn: &mut PgConnection, json_data: &str) -> Result<i32> {
+        let json_data = resolve_duplicates(json_data, self.duplicate_keys)?;
+        let row = self.insert_query(&json_data, None)?.fetch_one(conn).await?;
+        Ok(row.try_get("id")?)
+    }
+
+    fn insert_query<'q>(
+        &self,
+        json_data: &'q str,
+        doc_type: Option<&'q str>,
+    ) -> Result<Query<'q, Postgres, PgArguments>> {
+        Ok(match self.strategy {
+            StorageStrategy::Dual if self.jsonb_is_generated() => {
+                let content_hash = jcs::content_hash_exact_str(json_data)?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO json_test (raw_text, content_hash, doc_type)
+                    VALUES ($1, $2, $3)
+                    RETURNING id
+                    "#,
+                )
+                .bind(json_data)
+                .bind(content_hash)
+                .bind(doc_type)
+            }
+            StorageStrategy::Dual => {
+                let parsed_value: Value = serde_json::from_str(json_data)?;
+                let content_hash = jcs::content_hash_exact(&parsed_value);
+                sqlx::query(
+                    r#"
+                    INSERT INTO json_test (data_jsonb, raw_text, content_hash, doc_type)
+                    VALUES ($1, $2, $3, $4)
+                    RETURNING id
+                    "#,
+                )
+                .bind(parsed_value)
+                .bind(json_data)
+                .bind(content_hash)
+                .bind(doc_type)
+            }
+            StorageStrategy::Text => {
+                serde_json::from_str::<Value>(json_data)?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO json_text_test (raw_text, doc_type)
+                    VALUES ($1, $2)
+                    RETURNING id
+                    "#,
+                )
+                .bind(json_data)
+                .bind(doc_type)
+            }
+            StorageStrategy::Json => {
+                serde_json::from_str::<Value>(json_data)?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO json_json_test (data_json, doc_type)
+                    VALUES ($1::json, $2)
+                    RETURNING id
+                    "#,
+                )
+                .bind(json_data)
+                .bind(doc_type)
+            }
+            StorageStrategy::JsonbManifest => {
+                let document: OrderedJson = json_data.parse()?;
+                let manifest = KeyOrderManifest::from_document(&document);
+                sqlx::query(
+                    r#"
+                    INSERT INTO json_manifest_test (data_jsonb, key_order, doc_type)
+                    VALUES ($1, $2, $3)
+                    RETURNING id
+                    "#,
+                )
+                .bind(Value::from(document))
+                .bind(Json(manifest))
+                .bind(doc_type)
+            }
+        })
+    }
+
+    /// Applies `patch` to document `id` and stores the result, returning the
+    /// updated row.
+    ///
+    /// The row is locked with `FOR UPDATE` and every column the strategy keeps
+    /// (`raw_text`, `data_jsonb`, the manifest and the content hash) is
+    /// rewritten in the same transaction. Nothing is written if an operation
+    /// fails, including a failed `test`.
+    pub async fn patch_json(&self, id: i32, patch: &Patch) -> Result<StoredJson> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            "SELECT {} FROM {} WHERE id = $1 FOR UPDATE",
+            self.strategy.columns(),
+            self.strategy.table()
+        );
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(StoreError::NotFound(id))?;
+        let stored = StoredJson::from_row(&row, self.strategy)?;
+        stored.check_content_hash()?;
+
+        // Edit the raw text in place where there is one, so formatting
+        // outside the patched values survives.
+        let json_data = match &stored.raw_text {
+            Some(raw_text) => {
+                let mut document = LosslessJson::parse(raw_text.as_str())?;
+                patch.apply(&mut document)?;
+                document.into_string()
+            }
+            None => {
+                let mut document = stored.original_document()?;
+                patch.apply(&mut document)?;
+                document.to_string_pretty()
+            }
+        };
+
+        self.update_query(id, &json_data)?.execute(&mut *tx).await?;
+        let sql = format!(
+            "SELECT {} FROM {} WHERE id = $1",
+            self.strategy.columns(),
+            self.strategy.table()
+        );
+        let row = sqlx::query(&sql).bind(id).fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        self.stored_from_row(&row)
+    }
+
+    fn update_query<'q>(
+        &self,
+        id: i32,
+        json_data: &'q str,
+    ) -> Result<Query<'q, Postgres, PgArguments>> {
+        let sql = match self.strategy {
+            StorageStrategy::Dual if self.jsonb_is_generated() => "UPDATE json_test SET raw_text = $2, content_hash = $3 WHERE id = $1",
+            StorageStrategy::Dual => "UPDATE json_test SET data_jsonb = $2, raw_text = $3, content_hash = $4 WHERE id = $1",
+            StorageStrategy::Text => "UPDATE json_text_test SET raw_text = $2 WHERE id = $1",
+            StorageStrategy::Json => "UPDATE json_json_test SET data_json = $2::json WHERE id = $1",
+            StorageStrategy::JsonbManifest => "UPDATE json_manifest_test SET data_jsonb = $2, key_order = $3 WHERE id = $1",
+        };
+        let query = sqlx::query(sql).bind(id);
+        Ok(match self.strategy {
+            StorageStrategy::Dual if self.jsonb_is_generated() => query
+                .bind(json_data)
+                .bind(jcs::content_hash_exact_str(json_data)?),
+            StorageStrategy::Dual => {
+                let parsed_value: Value = serde_json::from_str(json_data)?;
+                let content_hash = jcs::content_hash_exact(&parsed_value);
+                query.bind(parsed_value).bind(json_data).bind(content_hash)
+            }
+            StorageStrategy::Text | StorageStrategy::Json => query.bind(json_data),
+            StorageStrategy::JsonbManifest => {
+                let document: OrderedJson = json_data.parse()?;
+                let manifest = KeyOrderManifest::from_document(&document);
+                query.bind(Value::from(document)).bind(Json(manifest))
+            }
+        })
+    }
+
+    /// Serializes `document` with its fields in `Serialize` order and stores it.
+    pub async fn insert_document<T: Serialize + ?Sized>(&self, document: &T) -> Result<i32> {
+        let json_data = serde_json::to_string_pretty(document)?;
+        self.insert_json(&json_data).await
+    }
+
+    /// Stores `document` under `T::DOC_TYPE`, with its keys in declaration
+    /// order at every level, nested documents and `Vec` elements included.
+    pub async fn insert_ordered<T: OrderedDocument>(&self, document: &T) -> Result<i32> {
+        let value = serde_json::to_value(document)?;
+        let ordered = T::ordering_schema().apply(&value, UnknownKeys::Append)?;
+        let json_data = serde_json::to_string_pretty(&ordered)?;
+        self.insert_typed_json(T::DOC_TYPE, &json_data).await
+    }
+
+    /// Reads back a document stored with [`insert_ordered`](Self::insert_ordered),
+    /// failing with [`StoreError::DocTypeMismatch`] if row `id` holds another type.
+    pub async fn get_ordered<T: OrderedDocument>(&self, id: i32) -> Result<T> {
+        let stored = self.get_json_by_id(id).await?;
+        if stored.doc_type.as_deref() != Some(T::DOC_TYPE) {
+            return Err(StoreError::DocTypeMismatch {
+                id,
+                expected: T::DOC_TYPE,
+                found: stored.doc_type,
+            });
+        }
+        Ok(serde_json::from_value(stored.document()?.into())?)
+    }
+
+    /// Fails with [`StoreError::HashMismatch`] if `raw_text` no longer matches
+    /// the content hash recorded at insert time, and with
+    /// [`StoreError::UnknownKey`] if the document's ordering schema rejects
+    /// one of its keys.
+    pub async fn get_json_by_id(&self, id: i32) -> Result<StoredJson> {
+        let sql = format!(
+            "SELECT {} FROM {} WHERE id = $1",
+            self.strategy.columns(),
+            self.strategy.table()
+        );
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(StoreError::NotFound(id))?;
+
+        let stored = StoredJson::from_row(&row, self.strategy)?;
+        stored.check_content_hash()?;
+        self.apply_schema(stored)
+    }
+
+    fn apply_schema(&self, mut stored: StoredJson) -> Result<StoredJson> {
+        let registered = stored
+            .doc_type
+            .as_deref()
+            .and_then(|doc_type| self.schemas.get(doc_type));
+        if let Some((schema, unknown_keys)) = registered {
+            let ordered = schema.apply(&stored.document()?.into(), unknown_keys)?;
+            stored.schema_ordered = Some(ordered.into());
+        }
+        Ok(stored)
+    }
+
+    /// Ids of stored documents whose canonical form hashes to `content_hash`,
+    /// i.e. that are identical up to whitespace and key order.
+    pub async fn find_by_content_hash(&self, content_hash: &str) -> Result<Vec<i32>> {
+        let rows = sqlx::query("SELECT id FROM json_test WHERE content_hash = $1 ORDER BY id")
+            .bind(content_hash)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(|row| Ok(row.try_get("id")?)).collect()
+    }
+
+    /// Returns the subtree of document `id` at JSON Pointer `pointer`. Where
+    /// the raw text is kept it is parsed with [`SpannedJson`], so the
+    /// fragment comes with its byte span and original formatting.
+    pub async fn get_fragment(&self, id: i32, pointer: &str) -> Result<Fragment> {
+        if split_pointer(pointer).is_none() {
+            return Err(StoreError::InvalidPointer(pointer.to_string()));
+        }
+        let not_found = || StoreError::PointerNotFound {
+            id,
+            pointer: pointer.to_string(),
+        };
+        let stored = self.get_json_by_id(id).await?;
+
+        let Some(raw_text) = &stored.raw_text else {
+            let document = stored.document()?;
+            let value = document.pointer(pointer).ok_or_else(not_found)?;
+            return Ok(Fragment {
+                id,
+                pointer: pointer.to_string(),
+                value: value.clone(),
+                span: None,
+                text: None,
+            });
+        };
+
+        let parsed = SpannedJson::parse(raw_text)?;
+        let node = parsed.pointer(pointer).ok_or_else(not_found)?;
+        Ok(Fragment {
+            id,
+            pointer: pointer.to_string(),
+            value: node.to_ordered(),
+            span: Some(node.span.clone()),
+            text: Some(node.text(raw_text).to_string()),
+        })
+    }
+
+    /// Deserializes the stored document `id` into `T`.
+    pub async fn get_document<T: DeserializeOwned>(&self, id: i32) -> Result<T> {
+        let document = self.get_json_by_id(id).await?.document()?;
+        Ok(serde_json::from_value(document.into())?)
+    }
+
+    pub async fn list(&self) -> Result<Vec<StoredJson>> {
+        let sql = format!(
+            "SELECT {} FROM {} ORDER BY id",
+            self.strategy.columns(),
+            self.strategy.table()
+        );
+        let rows = sqlx::query(&sql).fetch_all(&self.pool).await?;
+
+        rows.iter().map(|row| self.stored_from_row(row)).collect()
+    }
+
+    /// Starts a query on the `data_jsonb` column of the strategy's table.
+    pub fn query(&self) -> DocumentQuery<'_> {
+        DocumentQuery::new(self)
+    }
+
+    pub(crate) fn stored_from_row(&self, row: &PgRow) -> Result<StoredJson> {
+        self.apply_schema(StoredJson::from_row(row, self.strategy)?)
+    }
+
+    /// Returns `false` if no row with `id` existed.
+    pub async fn delete(&self, id: i32) -> Result<bool> {
+        let sql = format!("DELETE FROM {} WHERE id = $1", self.strategy.table());
+        let result = sqlx::query(&sql).bind(id).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Reads `file_path` and checks that it holds valid JSON, returning the text
+/// untouched.
+pub fn read_json_file(file_path: &str) -> Result<String> {
+    read_json_file_with(file_path, DuplicateKeys::PreserveAll)
+}
+
+/// Like [`read_json_file`], but resolves repeated object keys by `policy`
+/// the way [`OrderedJsonStore::insert_json`] does.
+pub fn read_json_file_with(file_path: &str, policy: DuplicateKeys) -> Result<String> {
+    let json_content = fs::read_to_string(file_path).map_err(|source| StoreError::Io {
+        path: file_path.to_string(),
+        source,
+    })?;
+
+    serde_json::from_str::<Value>(&json_content)?;
+
+    Ok(resolve_duplicates(&json_content, policy)?.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn jsonb_row(strategy: StorageStrategy, key_order: Option<KeyOrderManifest>) -> StoredJson {
+        StoredJson {
+            id: 4,
+            strategy,
+            data_jsonb: Some(json!({"b": 1, "a": 2})),
+            raw_text: None,
+            key_order,
+            content_hash: None,
+            doc_type: None,
+            schema_ordered: None,
+        }
+    }
+
+    #[test]
+    fn manifest_rows_need_their_manifest() {
+        let original: OrderedJson = r#"{"b": 1, "a": 2}"#.parse().unwrap();
+        let manifest = KeyOrderManifest::from_document(&original);
+        assert_eq!(
+            jsonb_row(StorageStrategy::JsonbManifest, Some(manifest))
+                .original_document()
+                .unwrap(),
+            original
+        );
+        assert!(matches!(
+            jsonb_row(StorageStrategy::JsonbManifest, None).original_document(),
+            Err(StoreError::MissingKeyOrder(4))
+        ));
+    }
+}
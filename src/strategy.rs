@@ -0,0 +1,150 @@
+use crate::error::StoreError;
+use std::fmt;
+use std::str::FromStr;
+
+/// How a document is laid out in PostgreSQL. Each strategy has its own table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StorageStrategy {
+    /// `jsonb` for querying plus the raw text (`json_test`).
+    #[default]
+    Dual,
+    /// Raw text only (`json_text_test`).
+    Text,
+    /// PostgreSQL's `json` type, which stores the input text verbatim
+    /// (`json_json_test`).
+    Json,
+    /// `jsonb` plus a [`KeyOrderManifest`](crate::KeyOrderManifest)
+    /// (`json_manifest_test`).
+    JsonbManifest,
+}
+
+impl StorageStrategy {
+    pub const ALL: [StorageStrategy; 4] = [
+        StorageStrategy::Dual,
+        StorageStrategy::Text,
+        StorageStrategy::Json,
+        StorageStrategy::JsonbManifest,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageStrategy::Dual => "dual",
+            StorageStrategy::Text => "text",
+            StorageStrategy::Json => "json",
+            StorageStrategy::JsonbManifest => "jsonb-manifest",
+        }
+    }
+
+    pub fn table(&self) -> &'static str {
+        match self {
+            StorageStrategy::Dual => "json_test",
+            StorageStrategy::Text => "json_text_test",
+            StorageStrategy::Json => "json_json_test",
+            StorageStrategy::JsonbManifest => "json_manifest_test",
+        }
+    }
+
+    /// Whether documents read back keep their original key order. Every
+    /// strategy does: `jsonb-manifest` rebuilds it from the `key_order`
+    /// manifest stored with each row, and a row without one is an error
+    /// ([`StoreError::MissingKeyOrder`](crate::StoreError::MissingKeyOrder))
+    /// rather than a document in `jsonb` order.
+    pub fn preserves_order(&self) -> bool {
+        true
+    }
+
+    /// Whether the original text is returned byte for byte.
+    pub fn preserves_text(&self) -> bool {
+        !matches!(self, StorageStrategy::JsonbManifest)
+    }
+
+    /// Whether the table has a `jsonb` column that can be indexed and queried.
+    pub fn has_jsonb(&self) -> bool {
+        matches!(self, StorageStrategy::Dual | StorageStrategy::JsonbManifest)
+    }
+
+    /// Select list mapping the strategy's table onto the columns read by
+    /// `StoredJson`.
+    pub(crate) fn columns(&self) -> &'static str {
+        match self {
+            StorageStrategy::Dual => {
+                "id, data_jsonb, raw_text, NULL::jsonb AS key_order, content_hash, doc_type"
+            }
+            StorageStrategy::Text => {
+                "id, NULL::jsonb AS data_jsonb, raw_text, NULL::jsonb AS key_order, NULL::text AS content_hash, doc_type"
+            }
+            StorageStrategy::Json => {
+                "id, NULL::jsonb AS data_jsonb, data_json::text AS raw_text, NULL::jsonb AS key_order, NULL::text AS content_hash, doc_type"
+            }
+            StorageStrategy::JsonbManifest => {
+                "id, data_jsonb, NULL::text AS raw_text, key_order, NULL::text AS content_hash, doc_type"
+            }
+        }
+    }
+}
+
+impl fmt::Display for StorageStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for StorageStrategy {
+    type Err = StoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StorageStrategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.as_str() == s)
+            .ok_or_else(|| StoreError::UnknownStrategy(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for strategy in StorageStrategy::ALL {
+            assert_eq!(
+                strategy.to_string().parse::<StorageStrategy>().unwrap(),
+                strategy
+            );
+        }
+        assert!(matches!(
+            "jsonb".parse::<StorageStrategy>(),
+            Err(StoreError::UnknownStrategy(name)) if name == "jsonb"
+        ));
+    }
+
+    #[test]
+    fn each_strategy_has_its_own_table() {
+        let tables: std::collections::HashSet<&str> = StorageStrategy::ALL
+            .iter()
+            .map(StorageStrategy::table)
+            .collect();
+        assert_eq!(tables.len(), StorageStrategy::ALL.len());
+    }
+
+    #[test]
+    fn only_jsonb_tables_can_be_queried() {
+        let queryable: Vec<StorageStrategy> = StorageStrategy::ALL
+            .into_iter()
+            .filter(StorageStrategy::has_jsonb)
+            .collect();
+        assert_eq!(
+            queryable,
+            [StorageStrategy::Dual, StorageStrategy::JsonbManifest]
+        );
+        assert!(!StorageStrategy::JsonbManifest.preserves_text());
+        assert!(StorageStrategy::Json.preserves_text());
+    }
+
+    #[test]
+    fn every_strategy_preserves_order() {
+        assert!(StorageStrategy::ALL
+            .iter()
+            .all(StorageStrategy::preserves_order));
+    }
+}
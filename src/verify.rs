@@ -0,0 +1,466 @@
+use crate::error::Result;
+use crate::ordered::{join_pointer, OrderedJson};
+use crate::span::{SpannedJson, SpannedKind};
+use serde::Serialize;
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// An object whose keys came back in a different order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyReordering {
+    pub pointer: String,
+    pub original: Vec<String>,
+    pub retrieved: Vec<String>,
+}
+
+/// Result of comparing an original document with a retrieved representation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VerificationReport {
+    pub objects_checked: usize,
+    pub reordered: Vec<KeyReordering>,
+    /// Whether both sides hold the same values, ignoring key order.
+    pub semantically_equal: bool,
+}
+
+impl VerificationReport {
+    pub fn order_preserved(&self) -> bool {
+        self.reordered.is_empty()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.order_preserved() && self.semantically_equal
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.order_preserved() {
+            write!(
+                f,
+                "key order preserved ({} objects checked)",
+                self.objects_checked
+            )?;
+        } else {
+            write!(
+                f,
+                "{} of {} objects reordered",
+                self.reordered.len(),
+                self.objects_checked
+            )?;
+        }
+        if self.semantically_equal {
+            write!(f, ", values equal")?;
+        } else {
+            write!(f, ", VALUES DIFFER")?;
+        }
+        for reordering in &self.reordered {
+            let pointer = if reordering.pointer.is_empty() {
+                "(root)"
+            } else {
+                &reordering.pointer
+            };
+            write!(
+                f,
+                "\n  {}: [{}] -> [{}]",
+                pointer,
+                reordering.original.join(", "),
+                reordering.retrieved.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares `original` with `retrieved` object by object.
+pub fn verify(original: &OrderedJson, retrieved: &OrderedJson) -> VerificationReport {
+    let mut report = VerificationReport {
+        objects_checked: 0,
+        reordered: Vec::new(),
+        semantically_equal: semantically_equal(&Value::from(original), &Value::from(retrieved)),
+    };
+    compare(original, retrieved, String::new(), &mut report);
+    report
+}
+
+fn compare(
+    original: &OrderedJson,
+    retrieved: &OrderedJson,
+    pointer: String,
+    report: &mut VerificationReport,
+) {
+    match (original, retrieved) {
+        (OrderedJson::Object(left), OrderedJson::Object(right)) => {
+            report.objects_checked += 1;
+            if !left.keys().eq(right.keys()) {
+                report.reordered.push(KeyReordering {
+                    pointer: pointer.clone(),
+                    original: left.keys().cloned().collect(),
+                    retrieved: right.keys().cloned().collect(),
+                });
+            }
+            for (key, child) in left {
+                if let Some(other) = right.get(key) {
+                    compare(child, other, join_pointer(&pointer, key), report);
+                }
+            }
+        }
+        (OrderedJson::Array(left), OrderedJson::Array(right)) => {
+            for (index, (child, other)) in left.iter().zip(right).enumerate() {
+                compare(
+                    child,
+                    other,
+                    join_pointer(&pointer, &index.to_string()),
+                    report,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Value equality ignoring key order and number spelling, so `1e2`, `100`
+/// and `100.0` are equal, as they are to PostgreSQL's `jsonb`.
+pub fn semantically_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => numbers_equal(a, b),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| semantically_equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| semantically_equal(a, b)))
+        }
+        _ => left == right,
+    }
+}
+
+/// Exact decimal equality of two numbers, whatever their spelling: no
+/// rounding to `f64`, so `12345678901234567890` and `12345678901234567891`
+/// differ while `1.50` and `15e-1` do not.
+pub fn numbers_equal(left: &Number, right: &Number) -> bool {
+    if left == right {
+        return true;
+    }
+    match (
+        Decimal::parse(&left.to_string()),
+        Decimal::parse(&right.to_string()),
+    ) {
+        (Some(left), Some(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// Exact decimal ordering of two numbers, as PostgreSQL's `numeric` orders
+/// them, or `None` if either is not a decimal number.
+pub(crate) fn compare_numbers(left: &Number, right: &Number) -> Option<Ordering> {
+    let left = Decimal::parse(&left.to_string())?;
+    let right = Decimal::parse(&right.to_string())?;
+    Some(left.cmp(&right))
+}
+
+/// `number` as its significant digits and a power of ten
+/// (`123.450` is `12345e-2`), spelled the same for every spelling of the
+/// same value.
+pub(crate) fn exact_number_text(number: &Number) -> String {
+    let text = number.to_string();
+    match Decimal::parse(&text) {
+        Some(decimal) if decimal.digits.is_empty() => "0".to_string(),
+        Some(decimal) => format!(
+            "{}{}e{}",
+            if decimal.negative { "-" } else { "" },
+            decimal.digits,
+            decimal.exponent
+        ),
+        None => text,
+    }
+}
+
+/// A number as sign, significant digits and the power of ten of the last
+/// digit, with leading and trailing zeros removed.
+#[derive(Debug, PartialEq, Eq)]
+struct Decimal {
+    negative: bool,
+    digits: String,
+    exponent: i128,
+}
+
+impl Decimal {
+    fn parse(text: &str) -> Option<Self> {
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i128>().ok()?),
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{}{}", integer, fraction);
+        let significant = digits.trim_start_matches('0').trim_end_matches('0');
+        if significant.is_empty() {
+            // -0 equals 0.
+            return Some(Decimal {
+                negative: false,
+                digits: String::new(),
+                exponent: 0,
+            });
+        }
+        let trailing_zeros = digits.len() - digits.trim_end_matches('0').len();
+        Some(Decimal {
+            negative,
+            digits: significant.to_string(),
+            exponent: exponent - fraction.len() as i128 + trailing_zeros as i128,
+        })
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = |d: &Decimal| match (d.digits.is_empty(), d.negative) {
+            (true, _) => 0,
+            (false, true) => -1,
+            (false, false) => 1,
+        };
+        let magnitude = || {
+            // Compare the position of the leading digit, then the digits.
+            let scale = |d: &Decimal| d.digits.len() as i128 + d.exponent;
+            scale(self)
+                .cmp(&scale(other))
+                .then_with(|| self.digits.cmp(&other.digits))
+        };
+        match sign(self).cmp(&sign(other)) {
+            Ordering::Equal if sign(self) == 0 => Ordering::Equal,
+            Ordering::Equal if self.negative => magnitude().reverse(),
+            Ordering::Equal => magnitude(),
+            ordering => ordering,
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A number spelled differently in the original text and in the stored
+/// value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NumberDifference {
+    pub pointer: String,
+    pub original: String,
+    pub stored: String,
+    /// Whether only the spelling differs (`1.0` vs `1`), not the value.
+    pub equal: bool,
+}
+
+impl fmt::Display for NumberDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "(root)"
+        } else {
+            &self.pointer
+        };
+        let kind = if self.equal { "respelled" } else { "CHANGED" };
+        write!(
+            f,
+            "{}: {} -> {} ({})",
+            pointer, self.original, self.stored, kind
+        )
+    }
+}
+
+/// Every number in `raw_text` whose text differs from the number at the same
+/// pointer of `stored`, e.g. `1e2` that `jsonb` returns as `100`. Of
+/// duplicate keys only the last occurrence is compared, as only it reaches
+/// `jsonb`.
+pub fn number_differences(raw_text: &str, stored: &Value) -> Result<Vec<NumberDifference>> {
+    let root = SpannedJson::parse(raw_text)?;
+    let mut differences = Vec::new();
+    collect_number_differences(raw_text, &root, stored, String::new(), &mut differences);
+    Ok(differences)
+}
+
+fn collect_number_differences(
+    source: &str,
+    original: &SpannedJson,
+    stored: &Value,
+    pointer: String,
+    out: &mut Vec<NumberDifference>,
+) {
+    match (&original.kind, stored) {
+        (SpannedKind::Number(a), Value::Number(b)) => {
+            let (original, stored) = (original.text(source), b.to_string());
+            if original != stored {
+                out.push(NumberDifference {
+                    pointer,
+                    original: original.to_string(),
+                    stored,
+                    equal: numbers_equal(a, b),
+                });
+            }
+        }
+        (SpannedKind::Object(members), Value::Object(right)) => {
+            let last: HashMap<&str, usize> = members
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (m.key.as_str(), i))
+                .collect();
+            for (index, member) in members.iter().enumerate() {
+                if let (true, Some(other)) =
+                    (last[member.key.as_str()] == index, right.get(&member.key))
+                {
+                    collect_number_differences(
+                        source,
+                        &member.value,
+                        other,
+                        join_pointer(&pointer, &member.key),
+                        out,
+                    );
+                }
+            }
+        }
+        (SpannedKind::Array(items), Value::Array(right)) => {
+            for (index, (child, other)) in items.iter().zip(right).enumerate() {
+                collect_number_differences(
+                    source,
+                    child,
+                    other,
+                    join_pointer(&pointer, &index.to_string()),
+                    out,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reordered_objects_are_reported_by_pointer() {
+        let original: OrderedJson =
+            r#"{"b": 1, "list": [{"y": 1, "x": 2}, {"k": 0}]}"#.parse().unwrap();
+        let retrieved: OrderedJson =
+            r#"{"b": 1, "list": [{"x": 2, "y": 1}, {"k": 0}]}"#.parse().unwrap();
+        let report = verify(&original, &retrieved);
+        assert_eq!(report.objects_checked, 3);
+        assert_eq!(
+            report.reordered,
+            [KeyReordering {
+                pointer: "/list/0".to_string(),
+                original: vec!["y".to_string(), "x".to_string()],
+                retrieved: vec!["x".to_string(), "y".to_string()],
+            }]
+        );
+        assert!(report.semantically_equal);
+        assert!(!report.order_preserved());
+        assert!(!report.passed());
+        assert_eq!(
+            report.to_string(),
+            "1 of 3 objects reordered, values equal\n  /list/0: [y, x] -> [x, y]"
+        );
+    }
+
+    #[test]
+    fn identical_documents_pass() {
+        let document: OrderedJson =
+            r#"{"z": {"b": [1, {"d": 0, "c": 0}]}, "a": null}"#.parse().unwrap();
+        let report = verify(&document, &document.clone());
+        assert!(report.passed());
+        assert_eq!(report.objects_checked, 3);
+        assert_eq!(
+            report.to_string(),
+            "key order preserved (3 objects checked), values equal"
+        );
+    }
+
+    #[test]
+    fn different_values_fail_even_in_order() {
+        let original: OrderedJson = r#"{"b": 1, "a": 2}"#.parse().unwrap();
+        let retrieved: OrderedJson = r#"{"a": 2, "b": 3}"#.parse().unwrap();
+        let report = verify(&original, &retrieved);
+        assert!(!report.semantically_equal);
+        assert_eq!(
+            report.to_string(),
+            "1 of 1 objects reordered, VALUES DIFFER\n  (root): [b, a] -> [a, b]"
+        );
+        assert!(!verify(&original, &r#"{"b": 1}"#.parse().unwrap()).passed());
+    }
+
+    #[test]
+    fn numbers_compare_by_exact_decimal_value() {
+        let number = |text: &str| serde_json::from_str::<Number>(text).unwrap();
+        for (a, b) in [
+            ("1", "1.0"),
+            ("1e2", "100"),
+            ("-0", "0.0"),
+            ("0.0150", "1.5e-2"),
+        ] {
+            assert!(numbers_equal(&number(a), &number(b)), "{} = {}", a, b);
+        }
+        for (a, b) in [
+            ("12345678901234567890", "12345678901234567891"),
+            ("1", "-1"),
+            ("1e400", "1e401"),
+        ] {
+            assert!(!numbers_equal(&number(a), &number(b)), "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn numbers_order_by_exact_decimal_value() {
+        let number = |text: &str| serde_json::from_str::<Number>(text).unwrap();
+        let ascending = [
+            "-1e400",
+            "-2",
+            "-1.5",
+            "-0.01",
+            "0",
+            "1e-400",
+            "0.19",
+            "0.2",
+            "12345678901234567890",
+            "12345678901234567891",
+            "1e400",
+        ];
+        for pair in ascending.windows(2) {
+            let (a, b) = (number(pair[0]), number(pair[1]));
+            assert_eq!(
+                compare_numbers(&a, &b),
+                Some(Ordering::Less),
+                "{} < {}",
+                a,
+                b
+            );
+            assert_eq!(
+                compare_numbers(&b, &a),
+                Some(Ordering::Greater),
+                "{} > {}",
+                b,
+                a
+            );
+        }
+        assert_eq!(
+            compare_numbers(&number("-0"), &number("0.0")),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn number_differences_compare_the_last_duplicate() {
+        let raw = r#"{"a": 1e2, "b": [1.50, 7], "a": 2.0}"#;
+        let stored = serde_json::from_str(r#"{"a": 2, "b": [1.50, 8]}"#).unwrap();
+        let differences = number_differences(raw, &stored).unwrap();
+        let shown: Vec<String> = differences.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            shown,
+            ["/b/1: 7 -> 8 (CHANGED)", "/a: 2.0 -> 2 (respelled)"]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! `#[derive(KeyOrder)]` against serde's own serialization order.
+
+use json_order_test::{KeyOrder, OrderedJson, OrderingSchema, UnknownKeys};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize, KeyOrder)]
+#[serde(rename_all = "camelCase")]
+struct Movie {
+    movie_title: String,
+    #[serde(rename = "released")]
+    year: u16,
+    #[serde(skip)]
+    #[allow(dead_code)]
+    cache: Option<String>,
+    cast: Vec<Actor>,
+    #[serde(flatten)]
+    rating: Rating,
+    tags: BTreeMap<String, Actor>,
+}
+
+#[derive(Serialize, KeyOrder)]
+struct Actor {
+    name: String,
+    role: String,
+}
+
+#[derive(Serialize, KeyOrder)]
+struct Rating {
+    score: f64,
+    votes: u32,
+}
+
+#[derive(Serialize, KeyOrder)]
+struct Wrapper<T>(T);
+
+#[derive(Serialize, KeyOrder)]
+struct Extensible {
+    id: u32,
+    #[serde(flatten)]
+    extra: HashMap<String, Actor>,
+}
+
+fn sample() -> Movie {
+    let actor = || Actor {
+        name: "Leo".to_string(),
+        role: "Cobb".to_string(),
+    };
+    Movie {
+        movie_title: "Inception".to_string(),
+        year: 2010,
+        cache: None,
+        cast: vec![actor()],
+        rating: Rating {
+            score: 8.8,
+            votes: 100,
+        },
+        tags: BTreeMap::from([("lead".to_string(), actor())]),
+    }
+}
+
+fn keys(schema: &OrderingSchema) -> Vec<&str> {
+    match schema {
+        OrderingSchema::Object { properties, .. } => {
+            properties.iter().map(|(key, _)| key.as_str()).collect()
+        }
+        other => panic!("expected an object schema, got {:?}", other),
+    }
+}
+
+#[test]
+fn derived_order_matches_serialization() {
+    let schema = Movie::ordering_schema();
+    assert_eq!(
+        keys(&schema),
+        ["movieTitle", "released", "cast", "score", "votes", "tags"]
+    );
+    let serialized = OrderedJson::from_serialize(&sample()).unwrap();
+    let serialized_keys: Vec<&str> = serialized
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(keys(&schema), serialized_keys);
+}
+
+#[test]
+fn derived_schemas_reorder_jsonb_output() {
+    let expected = serde_json::to_string(&sample()).unwrap();
+    // The same document as a `jsonb` column returns it: shortest keys first.
+    let jsonb = json!({
+        "cast": [{"name": "Leo", "role": "Cobb"}],
+        "tags": {"lead": {"role": "Cobb", "name": "Leo"}},
+        "score": 8.8,
+        "votes": 100,
+        "released": 2010,
+        "movieTitle": "Inception"
+    });
+    let ordered = Movie::ordering_schema()
+        .apply(&jsonb, UnknownKeys::Reject)
+        .unwrap();
+    assert_eq!(ordered.to_string(), expected);
+}
+
+#[test]
+fn newtypes_and_generics_use_the_inner_schema() {
+    assert_eq!(
+        Wrapper::<Vec<Actor>>::ordering_schema(),
+        OrderingSchema::array(Actor::ordering_schema())
+    );
+    assert_eq!(Wrapper::<String>::ordering_schema(), OrderingSchema::Any);
+}
+
+#[test]
+fn flattened_maps_accept_their_entries() {
+    let schema = Extensible::ordering_schema();
+    assert_eq!(keys(&schema), ["id"]);
+    let jsonb = json!({"id": 1, "lead": {"role": "Cobb", "name": "Leo"}});
+    let ordered = schema.apply(&jsonb, UnknownKeys::Reject).unwrap();
+    assert_eq!(
+        ordered.to_string(),
+        r#"{"id":1,"lead":{"name":"Leo","role":"Cobb"}}"#
+    );
+    assert!(Movie::ordering_schema()
+        .apply(&json!({"extra": 1}), UnknownKeys::Reject)
+        .is_err());
+}
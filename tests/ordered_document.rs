@@ -0,0 +1,47 @@
+//! `#[derive(OrderedDocument)]` without a database: the document type and
+//! the key order `insert_ordered` stores.
+
+use json_order_test::{KeyOrder, OrderedDocument, SchemaRegistry, UnknownKeys};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, KeyOrder, OrderedDocument)]
+struct Movie {
+    title: String,
+    year: u16,
+    director: Person,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, KeyOrder, OrderedDocument)]
+#[document(doc_type = "person.v2")]
+struct Person {
+    name: String,
+    born: u16,
+}
+
+#[test]
+fn doc_type_defaults_to_the_type_name() {
+    assert_eq!(Movie::DOC_TYPE, "Movie");
+    assert_eq!(Person::DOC_TYPE, "person.v2");
+}
+
+#[test]
+fn registered_documents_restore_declaration_order() {
+    let mut registry = SchemaRegistry::new();
+    registry.register_document::<Movie>(UnknownKeys::Reject);
+    let (schema, policy) = registry.get("Movie").unwrap();
+    assert_eq!(policy, UnknownKeys::Reject);
+
+    let jsonb = json!({
+        "year": 2010,
+        "title": "Inception",
+        "director": {"born": 1970, "name": "Christopher Nolan"}
+    });
+    let ordered = schema.apply(&jsonb, policy).unwrap();
+    assert_eq!(
+        ordered.to_string(),
+        r#"{"title":"Inception","year":2010,"director":{"name":"Christopher Nolan","born":1970}}"#
+    );
+    let movie: Movie = serde_json::from_value(ordered).unwrap();
+    assert_eq!(movie.director.name, "Christopher Nolan");
+}